@@ -0,0 +1,69 @@
+use serde::{Deserialize, Serialize};
+use std::time;
+
+use super::timer::human_duration;
+
+/// 実行中のタイマーを手動で増減する幅
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Adjust {
+    /// `+`と`-`で増減する時間
+    #[serde(with = "human_duration")]
+    step: time::Duration,
+    /// `>`と`<`で増減する時間
+    #[serde(with = "human_duration")]
+    large_step: time::Duration,
+}
+
+impl Default for Adjust {
+    fn default() -> Self {
+        Self {
+            step: time::Duration::from_secs(60),
+            large_step: time::Duration::from_secs(5 * 60),
+        }
+    }
+}
+
+impl Adjust {
+    /// 増減する幅
+    /// * large => `large_step`を使うか
+    pub fn amount(&self, large: bool) -> time::Duration {
+        if large {
+            self.large_step
+        } else {
+            self.step
+        }
+    }
+    /// * Err => (不正なフィールド名, 理由)
+    pub fn validate(&self) -> Result<(), (&'static str, String)> {
+        if self.step.is_zero() {
+            return Err(("step", "0秒より長い時間を指定してください".to_string()));
+        }
+        if self.large_step.is_zero() {
+            return Err((
+                "large_step",
+                "0秒より長い時間を指定してください".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn amount_uses_large_step() {
+        let adjust = Adjust::default();
+        assert_eq!(adjust.amount(false), time::Duration::from_secs(60));
+        assert_eq!(adjust.amount(true), time::Duration::from_secs(5 * 60));
+    }
+
+    #[test]
+    fn rejects_zero_steps() {
+        let adjust: Adjust = serde_json::from_str(r#"{ "large_step": "0s" }"#).unwrap();
+        assert_eq!(adjust.validate().unwrap_err().0, "large_step");
+        assert!(Adjust::default().validate().is_ok());
+    }
+}
@@ -0,0 +1,253 @@
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::path;
+
+use super::settings::{self, Settings, SettingsErr};
+
+/// バンドル内の設定ファイルの名前
+const CONFIG_ENTRY: &str = "config.json";
+/// バンドル内と設定ディレクトリ内で音声ファイルを置くディレクトリ
+const SOUNDS_DIR: &str = "sounds";
+
+#[derive(thiserror::Error, Debug)]
+pub enum BundleErr {
+    #[error("ファイルの読み書きに失敗しました")]
+    Io(#[from] std::io::Error),
+    #[error("zipファイルの読み書きに失敗しました: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("{0}")]
+    Settings(#[from] SettingsErr),
+    #[error("バンドルに{CONFIG_ENTRY}が含まれていません")]
+    NoConfig,
+    #[error("設定ディレクトリが見つかりません")]
+    NoConfigDir,
+    #[error("音声ファイル{0:?}を読み込めませんでした: {1}")]
+    Sound(path::PathBuf, std::io::Error),
+}
+
+/// 設定と、設定から参照している音声ファイルを1つのzipファイルにまとめる
+/// バンドル内の設定では、音声ファイルのパスを`sounds/<ファイル名>`に書き換える
+/// * 戻り値 => 見つからなかったためにバンドルに含めなかった音声ファイルのパス
+pub fn export(settings: &Settings, path: &path::Path) -> Result<Vec<String>, BundleErr> {
+    let mut settings = settings.clone();
+    // 元のパス -> バンドル内のパス
+    let mut sounds: BTreeMap<String, String> = BTreeMap::new();
+    let mut missing: Vec<String> = Vec::new();
+    for (_, other) in settings.others_mut() {
+        for sound in other.sounds_mut() {
+            if sound.is_empty() || missing.contains(sound) {
+                continue;
+            }
+            // 見つからない音声ファイルは元のパスのまま残す
+            if !settings::resolve_path(sound).exists() {
+                missing.push(sound.clone());
+                continue;
+            }
+            let entry = match sounds.get(sound.as_str()) {
+                Some(entry) => entry.clone(),
+                None => {
+                    let entry = unique_entry(sound, &sounds);
+                    sounds.insert(sound.clone(), entry.clone());
+                    entry
+                }
+            };
+            *sound = entry;
+        }
+    }
+
+    let mut zip = zip::ZipWriter::new(std::fs::File::create(path)?);
+    let options = zip::write::SimpleFileOptions::default();
+    zip.start_file(CONFIG_ENTRY, options)?;
+    zip.write_all(
+        serde_json::to_string_pretty(&settings)
+            .map_err(SettingsErr::from)?
+            .as_bytes(),
+    )?;
+    for (source, entry) in &sounds {
+        let source = settings::resolve_path(source);
+        let mut file =
+            std::fs::File::open(&source).map_err(|err| BundleErr::Sound(source.clone(), err))?;
+        zip.start_file(entry.as_str(), options)?;
+        std::io::copy(&mut file, &mut zip)?;
+    }
+    zip.finish()?;
+
+    Ok(missing)
+}
+
+/// ファイル名を拡張子の前と拡張子 (`.`を含む) に分ける
+fn split_name(source: &str) -> (String, String) {
+    let source = path::Path::new(source);
+    let stem = source
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "sound".to_string());
+    let extension = source
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+    (stem, extension)
+}
+
+/// 同じ名前の別のファイルと重ならないバンドル内のパス
+fn unique_entry(source: &str, sounds: &BTreeMap<String, String>) -> String {
+    let (stem, extension) = split_name(source);
+    let mut entry = format!("{}/{}{}", SOUNDS_DIR, stem, extension);
+    let mut n = 1;
+    while sounds.values().any(|used| used == &entry) {
+        n += 1;
+        entry = format!("{}/{}-{}{}", SOUNDS_DIR, stem, n, extension);
+    }
+    entry
+}
+
+/// バンドルから読み込んだ音声ファイル
+/// 取り込みを確定するまで設定ディレクトリには書き込まず、キャンセルした場合に既存のファイルを残す
+pub struct StagedSounds {
+    /// (設定ディレクトリからの相対パス, 内容)
+    files: Vec<(path::PathBuf, Vec<u8>)>,
+}
+
+impl StagedSounds {
+    /// 取り込むセクションから参照している音声ファイルだけを設定ディレクトリの`sounds`に書き込む
+    /// 内容の異なる同じ名前のファイルが既にあれば別の名前で書き込み、`settings`内のパスを書き換える
+    pub fn install(&self, settings: &mut Settings, sections: &[String]) -> Result<(), BundleErr> {
+        let config_dir = settings::config_dir().ok_or(BundleErr::NoConfigDir)?;
+        // バンドル内のパス -> 書き込んだパス
+        let mut installed: BTreeMap<String, String> = BTreeMap::new();
+        for (profile, other) in settings.others_mut() {
+            if !sections.contains(&format!("profiles.{}.other", profile)) {
+                continue;
+            }
+            for sound in other.sounds_mut() {
+                if let Some(target) = installed.get(sound.as_str()) {
+                    *sound = target.clone();
+                    continue;
+                }
+                let Some((_, contents)) = self
+                    .files
+                    .iter()
+                    .find(|(entry, _)| entry == path::Path::new(sound.as_str()))
+                else {
+                    continue;
+                };
+                let target = free_entry(&config_dir, sound, contents);
+                let output = config_dir.join(&target);
+                if !output.exists() {
+                    if let Some(parent_dir) = output.parent() {
+                        std::fs::create_dir_all(parent_dir)?;
+                    }
+                    std::fs::write(output, contents)?;
+                }
+                installed.insert(sound.clone(), target.clone());
+                *sound = target;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 設定ディレクトリ内で、内容の異なる既存のファイルと重ならない音声ファイルのパス
+/// 同じ内容のファイルが既にあれば、そのファイルのパス
+fn free_entry(config_dir: &path::Path, entry: &str, contents: &[u8]) -> String {
+    let (stem, extension) = split_name(entry);
+    let mut target = entry.to_string();
+    let mut n = 1;
+    loop {
+        let output = config_dir.join(&target);
+        if !output.exists() || std::fs::read(&output).is_ok_and(|existing| existing == contents) {
+            return target;
+        }
+        n += 1;
+        target = format!("{}/{}-{}{}", SOUNDS_DIR, stem, n, extension);
+    }
+}
+
+/// バンドル内の設定と音声ファイルを読み込む。音声ファイルは`StagedSounds::install`で展開する
+/// 音声ファイルのパスは設定ディレクトリからの相対パスのまま残るため、どの環境でも同じ設定で動作する
+pub fn import(path: &path::Path) -> Result<(Settings, StagedSounds), BundleErr> {
+    let mut zip = zip::ZipArchive::new(std::fs::File::open(path)?)?;
+
+    let mut files = Vec::new();
+    for index in 0..zip.len() {
+        let mut file = zip.by_index(index)?;
+        // バンドルの外に書き込むような不正なパスは無視する
+        let Some(entry) = file.enclosed_name() else {
+            continue;
+        };
+        if !file.is_file() || !entry.starts_with(SOUNDS_DIR) {
+            continue;
+        }
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)?;
+        files.push((entry, contents));
+    }
+
+    let mut input = String::new();
+    zip.by_name(CONFIG_ENTRY)
+        .map_err(|_| BundleErr::NoConfig)?
+        .read_to_string(&mut input)?;
+    Ok((Settings::from_json(&input)?.0, StagedSounds { files }))
+}
+
+/// バンドルとして扱うファイルかどうか
+pub fn is_bundle(path: &path::Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("zip"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exports_and_installs_referenced_sounds() {
+        settings::with_config_dir("bundle", b"", || {
+            let dir = settings::config_dir().unwrap();
+            std::fs::write(dir.join("bell.mp3"), b"bundled").unwrap();
+            let mut current = Settings::template();
+            current
+                .other_mut()
+                .set_finish_sound(path::Path::new("bell.mp3"));
+            current
+                .other_mut()
+                .set_restart_sound(path::Path::new("missing.mp3"));
+
+            // 見つからない音声ファイルは含めずに書き出す
+            let bundle = dir.join("bundle.zip");
+            let missing = export(&current, &bundle).unwrap();
+            assert_eq!(missing, vec!["missing.mp3".to_string()]);
+
+            std::fs::create_dir_all(dir.join(SOUNDS_DIR)).unwrap();
+            std::fs::write(dir.join("sounds/bell.mp3"), b"existing").unwrap();
+            let (incoming, sounds) = import(&bundle).unwrap();
+
+            // 取り込まないセクションの音声ファイルは書き込まない
+            let mut skipped = incoming.clone();
+            sounds.install(&mut skipped, &[]).unwrap();
+            assert!(!dir.join("sounds/bell-2.mp3").exists());
+
+            // 同じ名前で内容の異なるファイルは上書きせず、別の名前で書き込む
+            let mut installed = incoming.clone();
+            sounds
+                .install(&mut installed, &["profiles.default.other".to_string()])
+                .unwrap();
+            assert_eq!(
+                installed.other().get_finish_sound().unwrap(),
+                "sounds/bell-2.mp3"
+            );
+            assert_eq!(
+                installed.other().get_restart_sound().unwrap(),
+                "missing.mp3"
+            );
+            assert_eq!(
+                std::fs::read(dir.join("sounds/bell.mp3")).unwrap(),
+                b"existing"
+            );
+            assert_eq!(
+                std::fs::read(dir.join("sounds/bell-2.mp3")).unwrap(),
+                b"bundled"
+            );
+        });
+    }
+}
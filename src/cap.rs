@@ -0,0 +1,171 @@
+use serde::{Deserialize, Serialize};
+use std::time;
+
+use super::timer::{human_duration, human_duration_option};
+
+/// ソフト上限を超えた後のリマインドの間隔の最小値
+const MIN_REMIND_INTERVAL: time::Duration = time::Duration::from_secs(60);
+
+/// 耐久モードの時間の上限
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct EnduranceCap {
+    /// この時間に達したら自動で休憩モードに入る。省略した場合は上限なし
+    #[serde(with = "human_duration_option")]
+    hard: Option<time::Duration>,
+    /// `hard`に達するこの時間前に警告する
+    #[serde(with = "human_duration")]
+    warn_before: time::Duration,
+    /// この時間を超えたらリマインドを始める。省略した場合はリマインドしない
+    #[serde(with = "human_duration_option")]
+    soft: Option<time::Duration>,
+    /// `soft`を超えた後の最初のリマインドの間隔。リマインドのたびに半分になる (1分まで)
+    #[serde(with = "human_duration")]
+    soft_interval: time::Duration,
+}
+
+impl Default for EnduranceCap {
+    fn default() -> Self {
+        Self {
+            hard: None,
+            warn_before: time::Duration::from_secs(5 * 60),
+            soft: None,
+            soft_interval: time::Duration::from_secs(10 * 60),
+        }
+    }
+}
+
+/// 耐久モードの間の上限の警告とリマインドの状態
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CapState {
+    warned: bool,
+    reminders: u32,
+    next_reminder: Option<time::Duration>,
+}
+
+/// 耐久モードの時間が上限に近づいた、または超えたときの知らせ
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CapEvent {
+    /// `hard`まで残りこの時間
+    Warn(time::Duration),
+    /// `hard`に達したため休憩に入る
+    Force,
+    /// `soft`をこの時間超えている
+    Remind(time::Duration),
+}
+
+impl EnduranceCap {
+    /// 耐久モードの経過時間`elapsed`が`hard`に達しているか
+    pub fn reached(&self, elapsed: time::Duration) -> bool {
+        self.hard.is_some_and(|hard| elapsed >= hard)
+    }
+    /// 耐久モードの経過時間`elapsed`に対して、新たに知らせることがあれば返す
+    pub fn check(&self, state: &mut CapState, elapsed: time::Duration) -> Option<CapEvent> {
+        if let Some(hard) = self.hard {
+            if elapsed >= hard {
+                return Some(CapEvent::Force);
+            }
+            if !state.warned && elapsed + self.warn_before >= hard {
+                state.warned = true;
+                return Some(CapEvent::Warn(hard - elapsed));
+            }
+        }
+        if let Some(soft) = self.soft {
+            let next_reminder = *state.next_reminder.get_or_insert(soft);
+            if elapsed >= next_reminder {
+                // リマインドのたびに間隔を半分にして、長引くほど頻繁に知らせる
+                let interval = (self.soft_interval / 2u32.saturating_pow(state.reminders))
+                    .max(MIN_REMIND_INTERVAL);
+                state.reminders += 1;
+                state.next_reminder =
+                    Some((next_reminder + interval).max(elapsed + MIN_REMIND_INTERVAL));
+                return Some(CapEvent::Remind(elapsed - soft));
+            }
+        }
+        None
+    }
+    /// * Err => (不正なフィールド名, 理由)
+    pub fn validate(&self) -> Result<(), (&'static str, String)> {
+        if self.hard.is_some_and(|hard| hard.is_zero()) {
+            return Err(("hard", "0秒より長い時間を指定してください".to_string()));
+        }
+        if self.soft.is_some() && self.soft_interval.is_zero() {
+            return Err((
+                "soft_interval",
+                "0秒より長い時間を指定してください".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timer::minutes;
+
+    #[test]
+    fn hard_cap_warns_once_then_forces() {
+        let cap = EnduranceCap {
+            hard: Some(minutes(60)),
+            ..EnduranceCap::default()
+        };
+        let mut state = CapState::default();
+        assert_eq!(cap.check(&mut state, minutes(50)), None);
+        assert_eq!(
+            cap.check(&mut state, minutes(56)),
+            Some(CapEvent::Warn(minutes(4)))
+        );
+        assert_eq!(cap.check(&mut state, minutes(57)), None);
+        assert_eq!(cap.check(&mut state, minutes(60)), Some(CapEvent::Force));
+        assert!(cap.reached(minutes(60)));
+        assert!(!cap.reached(minutes(59)));
+    }
+
+    #[test]
+    fn soft_cap_reminds_more_often() {
+        let cap = EnduranceCap {
+            soft: Some(minutes(30)),
+            soft_interval: minutes(8),
+            ..EnduranceCap::default()
+        };
+        let mut state = CapState::default();
+        assert_eq!(cap.check(&mut state, minutes(29)), None);
+        assert_eq!(
+            cap.check(&mut state, minutes(30)),
+            Some(CapEvent::Remind(time::Duration::ZERO))
+        );
+        // 間隔は8分、4分、2分、1分と半分になり、1分より短くはならない
+        assert_eq!(cap.check(&mut state, minutes(37)), None);
+        assert_eq!(
+            cap.check(&mut state, minutes(38)),
+            Some(CapEvent::Remind(minutes(8)))
+        );
+        assert_eq!(
+            cap.check(&mut state, minutes(42)),
+            Some(CapEvent::Remind(minutes(12)))
+        );
+        assert_eq!(
+            cap.check(&mut state, minutes(44)),
+            Some(CapEvent::Remind(minutes(14)))
+        );
+        assert_eq!(
+            cap.check(&mut state, minutes(45)),
+            Some(CapEvent::Remind(minutes(15)))
+        );
+        assert_eq!(
+            cap.check(&mut state, minutes(46)),
+            Some(CapEvent::Remind(minutes(16)))
+        );
+    }
+
+    #[test]
+    fn no_cap_by_default() {
+        let mut state = CapState::default();
+        assert_eq!(
+            EnduranceCap::default().check(&mut state, minutes(600)),
+            None
+        );
+        assert!(EnduranceCap::default().validate().is_ok());
+    }
+}
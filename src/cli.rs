@@ -0,0 +1,65 @@
+/// コマンドライン引数
+#[derive(Debug, Default)]
+pub struct Args {
+    /// `--profile <NAME>`: 起動時に使用するプロファイル
+    pub profile: Option<String>,
+    /// `--preset <NAME>`: 使用中のプロファイルに適用するプリセット
+    pub preset: Option<String>,
+    /// `--list-presets`: プリセットの一覧を表示して終了する
+    pub list_presets: bool,
+    /// `--setup`: 初回起動時の設定の質問をもう一度行う
+    pub setup: bool,
+    /// `--preview`: 使用中のプロファイルの休憩時間を表示して終了する
+    pub preview: bool,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum CliErr {
+    #[error("{0}には値が必要です")]
+    MissingValue(&'static str),
+    #[error("{0:?}は不明な引数です")]
+    Unknown(String),
+    #[error("")]
+    Help,
+}
+
+pub const USAGE: &str = "\
+使い方: majitimer [OPTIONS]
+
+OPTIONS:
+    --profile <NAME>    使用するプロファイルを指定して起動します
+    --preset <NAME>     プリセットをプロファイルに適用して起動します
+    --list-presets      プリセットの一覧を表示します
+    --preview           休憩時間の下限・上限と丸めを適用した休憩時間を表示します
+    --setup             休憩時間の曲線を決める質問をもう一度行います
+    -h, --help          このヘルプを表示します";
+
+impl Args {
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, CliErr> {
+        let mut parsed = Self::default();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--profile" => {
+                    parsed.profile = Some(args.next().ok_or(CliErr::MissingValue("--profile"))?)
+                }
+                "--preset" => {
+                    parsed.preset = Some(args.next().ok_or(CliErr::MissingValue("--preset"))?)
+                }
+                "--list-presets" => parsed.list_presets = true,
+                "--setup" => parsed.setup = true,
+                "--preview" => parsed.preview = true,
+                "-h" | "--help" => return Err(CliErr::Help),
+                _ => {
+                    if let Some(profile) = arg.strip_prefix("--profile=") {
+                        parsed.profile = Some(profile.to_string());
+                    } else if let Some(preset) = arg.strip_prefix("--preset=") {
+                        parsed.preset = Some(preset.to_string());
+                    } else {
+                        return Err(CliErr::Unknown(arg));
+                    }
+                }
+            }
+        }
+        Ok(parsed)
+    }
+}
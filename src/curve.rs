@@ -0,0 +1,262 @@
+use serde::{Deserialize, Serialize};
+use std::time;
+
+use super::timer::{human_duration, human_duration_option, MyToType};
+
+/// 休憩時間の曲線を比較するときに使う耐久モードの時間 (分)
+pub const SAMPLE_MINUTES: [u64; 4] = [15, 30, 60, 120];
+
+/// 曲線を求めるのに必要な記録の最小数
+pub const FIT_MIN_SAMPLES: usize = 5;
+
+/// 耐久モードの時間から休憩時間を算出するロジスティック曲線
+/// r = L / (1 + e^(-k * (w - w_0)))
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct RestCurve {
+    /// 休憩時間の最大値
+    #[serde(with = "human_duration")]
+    pub l: time::Duration,
+    /// 曲線の傾き
+    pub k: f64,
+    /// 休憩時間が`l`の半分になる耐久モードの時間
+    #[serde(with = "human_duration")]
+    pub w0: time::Duration,
+}
+
+impl RestCurve {
+    /// 耐久モードで`w`だけ作業したときの休憩時間
+    pub fn rest(&self, w: time::Duration) -> time::Duration {
+        let w = w.as_secs_f64();
+        let r = self.l.as_secs_f64()
+            / (1.0 + std::f64::consts::E.powf(-self.k * (w - self.w0.as_secs_f64())));
+
+        time::Duration::from_secs_f64(r)
+    }
+    /// 休憩時間の最大値`l`と中心`w0`を決めたうえで、耐久モードで`at`だけ作業したときの休憩時間が`rest`になる曲線
+    /// 増加する曲線にならない組み合わせの場合は`None`
+    pub fn fit_slope(
+        l: time::Duration,
+        w0: time::Duration,
+        at: time::Duration,
+        rest: time::Duration,
+    ) -> Option<Self> {
+        let (l_secs, rest_secs) = (l.as_secs_f64(), rest.as_secs_f64());
+        if rest_secs <= 0.0 || rest_secs >= l_secs || at == w0 {
+            return None;
+        }
+        // rest = L / (1 + e^(-k * (at - w0))) を k について解く
+        let k = -(l_secs / rest_secs - 1.0).ln() / (at.as_secs_f64() - w0.as_secs_f64());
+        if k.is_finite() && k > 0.0 {
+            Some(Self { l, k, w0 })
+        } else {
+            None
+        }
+    }
+    /// (耐久モードの時間, 実際の休憩時間) の組に対する二乗平均平方根誤差 (秒)
+    pub fn rms_error(&self, samples: &[(time::Duration, time::Duration)]) -> f64 {
+        if samples.is_empty() {
+            return 0.0;
+        }
+        let sum: f64 = samples
+            .iter()
+            .map(|(w, r)| (self.rest(*w).as_secs_f64() - r.as_secs_f64()).powi(2))
+            .sum();
+        (sum / samples.len() as f64).sqrt()
+    }
+    /// (耐久モードの時間, 実際の休憩時間) の組に最もよく合う曲線を最小二乗法で求める
+    /// `w0`と`k`は格子状に探索し、それぞれについて`l`は誤差が最小になる値を直接求める
+    pub fn fit(samples: &[(time::Duration, time::Duration)]) -> Option<Self> {
+        if samples.len() < FIT_MIN_SAMPLES {
+            return None;
+        }
+        let points: Vec<(f64, f64)> = samples
+            .iter()
+            .map(|(w, r)| (w.as_secs_f64(), r.as_secs_f64()))
+            .collect();
+        let max_w = points.iter().map(|(w, _)| *w).fold(0.0, f64::max);
+
+        let mut best: Option<(f64, Self)> = None;
+        // w0は0秒から最長の作業時間の2倍まで1分刻み、kは1e-4から1e-1まで対数的に探索する
+        let w0_steps = ((max_w * 2.0) / 60.0).ceil().max(1.0) as u64;
+        for w0_step in 0..=w0_steps {
+            let w0 = w0_step as f64 * 60.0;
+            for k_step in 0..=60 {
+                let k = 1e-4 * 10f64.powf(k_step as f64 / 20.0);
+                let sigmoid = |w: f64| 1.0 / (1.0 + (-k * (w - w0)).exp());
+                let (numerator, denominator) = points.iter().fold((0.0, 0.0), |(n, d), (w, r)| {
+                    (n + r * sigmoid(*w), d + sigmoid(*w).powi(2))
+                });
+                if denominator <= 0.0 {
+                    continue;
+                }
+                let l = (numerator / denominator).max(0.0);
+                let error: f64 = points
+                    .iter()
+                    .map(|(w, r)| (l * sigmoid(*w) - r).powi(2))
+                    .sum();
+                if best
+                    .as_ref()
+                    .is_none_or(|(best_error, _)| error < *best_error)
+                {
+                    best = Some((
+                        error,
+                        Self {
+                            l: time::Duration::from_secs_f64(l),
+                            k,
+                            w0: time::Duration::from_secs_f64(w0),
+                        },
+                    ));
+                }
+            }
+        }
+        best.map(|(_, curve)| curve)
+            .filter(|curve| !curve.l.is_zero())
+    }
+    /// `SAMPLE_MINUTES`の各時間での、`bounds`を適用した休憩時間
+    pub fn samples(&self, bounds: &RestBounds) -> [time::Duration; SAMPLE_MINUTES.len()] {
+        SAMPLE_MINUTES.map(|minutes| {
+            let w = time::Duration::from_secs(minutes * 60);
+            bounds.apply(w, self.rest(w))
+        })
+    }
+    /// `SAMPLE_MINUTES`の各時間での休憩時間を1行にまとめた文字列
+    pub fn summary(&self, bounds: &RestBounds) -> String {
+        SAMPLE_MINUTES
+            .iter()
+            .zip(self.samples(bounds))
+            .map(|(minutes, rest)| format!("{}分→{}", minutes, rest.to_time_string()))
+            .collect::<Vec<_>>()
+            .join("  ")
+    }
+}
+
+/// 算出した休憩時間に適用する下限・上限と丸め
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct RestBounds {
+    /// 休憩時間の最小値
+    #[serde(with = "human_duration")]
+    pub min: time::Duration,
+    /// 休憩時間の最大値。省略した場合は上限なし
+    #[serde(with = "human_duration_option")]
+    pub max: Option<time::Duration>,
+    /// 作業時間がこれより短い場合は休憩時間を与えない
+    #[serde(with = "human_duration")]
+    pub dead_zone: time::Duration,
+    /// 休憩時間を1分単位に丸める
+    pub round_minutes: bool,
+}
+
+impl RestBounds {
+    /// 作業時間`w`に対して算出した休憩時間`rest`に、不感帯・丸め・下限と上限の順で適用する
+    pub fn apply(&self, w: time::Duration, rest: time::Duration) -> time::Duration {
+        if w < self.dead_zone {
+            return time::Duration::ZERO;
+        }
+        let mut rest = rest;
+        if self.round_minutes {
+            rest = time::Duration::from_secs((rest.as_secs_f64() / 60.0).round() as u64 * 60);
+        }
+        rest = rest.max(self.min);
+        match self.max {
+            Some(max) => rest.min(max),
+            None => rest,
+        }
+    }
+    /// * Err => (不正なフィールド名, 理由)
+    pub fn validate(&self) -> Result<(), (&'static str, String)> {
+        if self.max.is_some_and(|max| max < self.min) {
+            return Err(("max", "`min`以上の時間を指定してください".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// テストで使う、`fit`の探索する格子上のパラメーターを持つ曲線
+#[cfg(test)]
+pub fn grid_curve() -> RestCurve {
+    RestCurve {
+        l: time::Duration::from_secs(40 * 60),
+        k: 1e-4 * 10f64.powf(25.0 / 20.0),
+        w0: time::Duration::from_secs(40 * 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::grid_curve as curve;
+    use super::*;
+    use crate::timer::minutes;
+
+    #[test]
+    fn rest_is_half_of_l_at_w0() {
+        assert_eq!(curve().rest(minutes(40)), minutes(20));
+        assert!(curve().rest(minutes(10)) < curve().rest(minutes(60)));
+    }
+
+    #[test]
+    fn fit_slope_passes_through_the_point() {
+        let fitted = RestCurve::fit_slope(minutes(40), minutes(40), minutes(60), minutes(30))
+            .expect("増加する曲線になる");
+        let rest = fitted.rest(minutes(60)).as_secs_f64();
+        assert!((rest - minutes(30).as_secs_f64()).abs() < 1.0);
+
+        // 中心より後で半分より短い休憩は増加する曲線にならない
+        assert_eq!(
+            RestCurve::fit_slope(minutes(40), minutes(40), minutes(60), minutes(10)),
+            None
+        );
+        assert_eq!(
+            RestCurve::fit_slope(minutes(40), minutes(40), minutes(60), minutes(40)),
+            None
+        );
+    }
+
+    #[test]
+    fn fit_recovers_the_curve() {
+        let samples: Vec<_> = [10, 20, 30, 45, 60, 90]
+            .into_iter()
+            .map(|w| (minutes(w), curve().rest(minutes(w))))
+            .collect();
+        let fitted = RestCurve::fit(&samples).expect("十分な記録がある");
+        assert!(fitted.rms_error(&samples) < 1.0);
+        assert!((fitted.l.as_secs_f64() - curve().l.as_secs_f64()).abs() < 1.0);
+        assert_eq!(fitted.w0, curve().w0);
+
+        assert_eq!(RestCurve::fit(&samples[..FIT_MIN_SAMPLES - 1]), None);
+    }
+
+    #[test]
+    fn bounds_apply_in_order() {
+        let bounds = RestBounds {
+            min: minutes(5),
+            max: Some(minutes(30)),
+            dead_zone: minutes(10),
+            round_minutes: true,
+        };
+        assert_eq!(bounds.apply(minutes(9), minutes(20)), time::Duration::ZERO);
+        assert_eq!(
+            bounds.apply(minutes(10), time::Duration::from_secs(61)),
+            minutes(5)
+        );
+        assert_eq!(
+            bounds.apply(minutes(30), time::Duration::from_secs(10 * 60 + 31)),
+            minutes(11)
+        );
+        assert_eq!(bounds.apply(minutes(120), minutes(45)), minutes(30));
+        assert_eq!(
+            RestBounds::default().apply(time::Duration::ZERO, time::Duration::from_secs(61)),
+            time::Duration::from_secs(61)
+        );
+    }
+
+    #[test]
+    fn bounds_reject_max_below_min() {
+        let bounds = RestBounds {
+            min: minutes(10),
+            max: Some(minutes(5)),
+            ..RestBounds::default()
+        };
+        assert!(bounds.validate().is_err());
+    }
+}
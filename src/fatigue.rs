@@ -0,0 +1,144 @@
+use serde::{Deserialize, Serialize};
+use std::time;
+
+use super::history::{CycleRecord, SessionRecord};
+use super::timer::human_duration;
+
+/// その日の作業の累計に応じて休憩時間を延ばす項
+/// extra = per_hour * (今日の作業時間 - threshold) / 1時間 - recovery * 今日の休憩時間 (0から`max`まで)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Fatigue {
+    pub enabled: bool,
+    /// 疲労が溜まり始めるまでの1日の作業時間
+    #[serde(with = "human_duration")]
+    threshold: time::Duration,
+    /// `threshold`を超えた作業1時間あたりに延ばす休憩時間
+    #[serde(with = "human_duration")]
+    per_hour: time::Duration,
+    /// その日に既に取った休憩のうち、疲労の回復として差し引く割合 (0.0〜1.0)
+    recovery: f64,
+    /// 延ばす休憩時間の上限
+    #[serde(with = "human_duration")]
+    max: time::Duration,
+}
+
+impl Default for Fatigue {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threshold: time::Duration::from_secs(2 * 60 * 60),
+            per_hour: time::Duration::from_secs(5 * 60),
+            recovery: 0.25,
+            max: time::Duration::from_secs(20 * 60),
+        }
+    }
+}
+
+impl Fatigue {
+    /// 今日の作業と休憩の累計に対して延ばす休憩時間。無効の場合は0
+    pub fn extra(&self, today: &DayTotals) -> time::Duration {
+        if !self.enabled {
+            return time::Duration::ZERO;
+        }
+        let excess = today.work.saturating_sub(self.threshold).as_secs_f64();
+        let extra = self.per_hour.as_secs_f64() * excess / 3600.0
+            - self.recovery * today.rest.as_secs_f64();
+        time::Duration::from_secs_f64(extra.clamp(0.0, self.max.as_secs_f64()))
+    }
+    /// * Err => (不正なフィールド名, 理由)
+    pub fn validate(&self) -> Result<(), (&'static str, String)> {
+        if !(0.0..=1.0).contains(&self.recovery) {
+            return Err((
+                "recovery",
+                format!("0.0から1.0までの値を指定してください ({})", self.recovery),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// 1日の作業時間と休憩時間の累計
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DayTotals {
+    /// 休憩時間の算出に使った作業時間の合計
+    pub work: time::Duration,
+    /// 実際に休んだ時間の合計。記録が無いサイクルは算出された休憩時間を使う
+    pub rest: time::Duration,
+}
+
+impl DayTotals {
+    /// `date`に開始したセッションの記録の合計
+    pub fn from_sessions(sessions: &[SessionRecord], date: chrono::NaiveDate) -> Self {
+        let mut totals = Self::default();
+        for session in sessions
+            .iter()
+            .filter(|session| session.started_at.date_naive() == date)
+        {
+            totals.add_cycles(&session.cycles);
+        }
+        totals
+    }
+    pub fn add_cycles(&mut self, cycles: &[CycleRecord]) {
+        for cycle in cycles {
+            self.work += cycle.work();
+            self.rest += cycle.taken.unwrap_or(cycle.rest);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::cycle;
+    use crate::timer::minutes;
+    use crate::workflow::WorkflowKind;
+
+    fn fatigue() -> Fatigue {
+        Fatigue {
+            enabled: true,
+            threshold: minutes(60),
+            per_hour: minutes(10),
+            recovery: 0.5,
+            max: minutes(15),
+        }
+    }
+
+    #[test]
+    fn extra_grows_after_threshold() {
+        let today = |work, rest| DayTotals {
+            work: minutes(work),
+            rest: minutes(rest),
+        };
+        assert_eq!(fatigue().extra(&today(60, 0)), time::Duration::ZERO);
+        assert_eq!(fatigue().extra(&today(120, 0)), minutes(10));
+        // 休憩の半分を回復として差し引く
+        assert_eq!(fatigue().extra(&today(120, 10)), minutes(5));
+        assert_eq!(fatigue().extra(&today(120, 30)), time::Duration::ZERO);
+        assert_eq!(fatigue().extra(&today(300, 0)), minutes(15));
+
+        let disabled = Fatigue {
+            enabled: false,
+            ..fatigue()
+        };
+        assert_eq!(disabled.extra(&today(300, 0)), time::Duration::ZERO);
+    }
+
+    #[test]
+    fn totals_only_count_the_given_day() {
+        let mut today = SessionRecord::new("default", WorkflowKind::default());
+        today.cycles = vec![
+            cycle(minutes(30), minutes(5), Some(minutes(8))),
+            cycle(minutes(45), minutes(10), None),
+        ];
+        let mut yesterday = SessionRecord::new("default", WorkflowKind::default());
+        yesterday.started_at -= chrono::Duration::days(1);
+        yesterday.cycles = vec![cycle(minutes(60), minutes(15), None)];
+
+        let totals =
+            DayTotals::from_sessions(&[yesterday, today], chrono::Local::now().date_naive());
+        assert_eq!(totals.work, minutes(75));
+        // 実際に休んだ時間が無いサイクルは算出された休憩時間を使う
+        assert_eq!(totals.rest, minutes(18));
+    }
+}
@@ -0,0 +1,201 @@
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
+use super::timer::{human_duration, human_duration_option};
+use super::workflow::WorkflowKind;
+
+/// 1回分の耐久モードとそれに続く休憩の記録
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CycleRecord {
+    #[serde(with = "human_duration")]
+    pub endurance: std::time::Duration,
+    /// 休憩時間の算出に使った作業時間。設定によって本気モードや一時停止の時間を含む
+    #[serde(default, with = "human_duration_option")]
+    pub work: Option<std::time::Duration>,
+    /// 耐久モードの時間から算出された休憩時間
+    #[serde(with = "human_duration")]
+    pub rest: std::time::Duration,
+    /// 休憩モードの開始から、再び本気モードを始めるまでに実際に休んだ時間
+    #[serde(default, with = "human_duration_option")]
+    pub taken: Option<std::time::Duration>,
+    /// 一定のサイクルごとに取る長い休憩だったか
+    #[serde(default)]
+    pub long_break: bool,
+    /// 耐久モード中に記録したラップ
+    #[serde(default)]
+    pub laps: Vec<LapRecord>,
+    /// サイクルの間に手動で増減したタイマーの時間
+    #[serde(default)]
+    pub adjustments: Vec<AdjustRecord>,
+}
+
+/// 耐久モードやストップウォッチで記録したラップ
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LapRecord {
+    /// ラップを記録した時点の経過時間
+    #[serde(with = "human_duration")]
+    pub at: std::time::Duration,
+    /// ラップに付けた名前
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+/// 手動で時間を増減したタイマー
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AdjustTarget {
+    /// 本気モードの残り時間
+    Sprint,
+    /// 耐久モードの経過時間
+    Endurance,
+    /// 休憩モードの残り時間
+    Rest,
+    /// 本気モードの再開を促すリマインドまでの時間
+    Remind,
+}
+
+/// 手動でタイマーの時間を増減した記録
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AdjustRecord {
+    pub target: AdjustTarget,
+    #[serde(with = "human_duration")]
+    pub amount: std::time::Duration,
+    /// 時間を減らしたか
+    #[serde(default)]
+    pub decreased: bool,
+}
+
+/// タイマーを開始してからリセットまたは終了するまでの記録
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SessionRecord {
+    /// セッション中に使用していたプロファイルの名前
+    pub profile: String,
+    /// セッション中に使用していた手法
+    #[serde(default)]
+    pub workflow: WorkflowKind,
+    pub started_at: chrono::DateTime<chrono::Local>,
+    pub ended_at: Option<chrono::DateTime<chrono::Local>>,
+    pub cycles: Vec<CycleRecord>,
+    /// 休憩に入る前にセッションを終えたサイクルで記録したラップ
+    #[serde(default)]
+    pub laps: Vec<LapRecord>,
+    /// 休憩に入る前にセッションを終えたサイクルでの時間の調整
+    #[serde(default)]
+    pub adjustments: Vec<AdjustRecord>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum HistoryErr {
+    #[error("履歴ファイルの書き込みに失敗しました")]
+    Io(#[from] std::io::Error),
+    #[error("履歴の変換に失敗しました")]
+    Serde(#[from] serde_json::Error),
+    #[error("設定ディレクトリが見つかりません")]
+    NoConfigDir,
+}
+
+impl CycleRecord {
+    /// 休憩時間の算出に使った作業時間。記録が無い古い履歴では耐久モードの時間
+    pub fn work(&self) -> std::time::Duration {
+        self.work.unwrap_or(self.endurance)
+    }
+}
+
+impl SessionRecord {
+    pub fn new(profile: &str, workflow: WorkflowKind) -> Self {
+        Self {
+            profile: profile.to_string(),
+            workflow,
+            started_at: chrono::Local::now(),
+            ended_at: None,
+            cycles: Vec::new(),
+            laps: Vec::new(),
+            adjustments: Vec::new(),
+        }
+    }
+}
+
+/// 履歴ファイルのパス。1行に1セッションずつJSONで記録する
+pub fn path() -> Result<std::path::PathBuf, HistoryErr> {
+    Ok(super::settings::config_dir()
+        .ok_or(HistoryErr::NoConfigDir)?
+        .join("history.jsonl"))
+}
+
+/// 履歴ファイルから全てのセッションの記録を読み込む。読み込めない行は無視する
+pub fn load() -> Result<Vec<SessionRecord>, HistoryErr> {
+    let path = path()?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    Ok(std::fs::read_to_string(path)?
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// セッションの記録を履歴ファイルの末尾に追加する
+pub fn append(record: &SessionRecord) -> Result<(), HistoryErr> {
+    let path = path()?;
+    if let Some(parent_dir) = path.parent() {
+        std::fs::create_dir_all(parent_dir)?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{}", serde_json::to_string(record)?)?;
+
+    Ok(())
+}
+
+/// テストで作業時間と休憩時間だけを指定してサイクルの記録を作るための関数
+#[cfg(test)]
+pub fn cycle(
+    work: std::time::Duration,
+    rest: std::time::Duration,
+    taken: Option<std::time::Duration>,
+) -> CycleRecord {
+    CycleRecord {
+        endurance: work,
+        work: Some(work),
+        rest,
+        taken,
+        long_break: false,
+        laps: Vec::new(),
+        adjustments: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::with_config_dir;
+    use crate::timer::minutes;
+
+    #[test]
+    fn load_skips_unreadable_lines() {
+        with_config_dir("history", b"{ broken\n", || {
+            let mut session = SessionRecord::new("work", WorkflowKind::Pomodoro);
+            session.cycles.push(cycle(minutes(25), minutes(5), None));
+            append(&session).unwrap();
+            append(&SessionRecord::new("default", WorkflowKind::Maji)).unwrap();
+
+            let sessions = load().unwrap();
+            assert_eq!(sessions.len(), 2);
+            assert_eq!(sessions[0].profile, "work");
+            assert_eq!(sessions[0].workflow, WorkflowKind::Pomodoro);
+            assert_eq!(sessions[0].cycles[0].rest, minutes(5));
+        });
+    }
+
+    #[test]
+    fn old_records_use_endurance_as_work() {
+        let record: CycleRecord =
+            serde_json::from_str(r#"{ "endurance": "40m", "rest": "20m" }"#).unwrap();
+        assert_eq!(record.work(), minutes(40));
+        assert_eq!(record.taken, None);
+        assert!(record.laps.is_empty());
+        assert_eq!(cycle(minutes(30), minutes(10), None).work(), minutes(30));
+    }
+}
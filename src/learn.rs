@@ -0,0 +1,142 @@
+use std::time;
+
+use ratatui::style::Stylize;
+use ratatui::text::{Line, Span};
+
+use super::curve::{RestBounds, RestCurve, FIT_MIN_SAMPLES};
+use super::history::SessionRecord;
+use super::timer::MyToType;
+use super::workflow::WorkflowKind;
+
+#[derive(thiserror::Error, Debug)]
+pub enum LearnErr {
+    #[error("実際の休憩時間の記録が{0}件しかありません ({FIT_MIN_SAMPLES}件以上必要です)")]
+    NotEnoughSamples(usize),
+    #[error("記録に合う曲線が見つかりませんでした")]
+    NoFit,
+}
+
+/// 実際の休憩時間の記録から求めた曲線の提案
+pub struct Proposal {
+    current: RestCurve,
+    proposed: RestCurve,
+    /// (耐久モードの時間, 実際の休憩時間)
+    samples: Vec<(time::Duration, time::Duration)>,
+    /// 休憩時間のプレビューに適用する下限・上限と丸め
+    bounds: RestBounds,
+}
+
+impl Proposal {
+    /// `profile`の記録から、実際の休憩時間に合う曲線を求める
+    pub fn new(
+        sessions: &[SessionRecord],
+        profile: &str,
+        current: RestCurve,
+        bounds: RestBounds,
+    ) -> Result<Self, LearnErr> {
+        let samples: Vec<(time::Duration, time::Duration)> = sessions
+            .iter()
+            // 休憩時間の曲線を使うのは本気タイマーの手法だけ
+            .filter(|session| session.profile == profile && session.workflow == WorkflowKind::Maji)
+            .flat_map(|session| &session.cycles)
+            // 長い休憩は曲線とは別に決まるため、曲線を求めるのには使わない
+            .filter(|cycle| !cycle.long_break)
+            .filter_map(|cycle| cycle.taken.map(|taken| (cycle.work(), taken)))
+            .collect();
+        if samples.len() < FIT_MIN_SAMPLES {
+            return Err(LearnErr::NotEnoughSamples(samples.len()));
+        }
+        let proposed = RestCurve::fit(&samples).ok_or(LearnErr::NoFit)?;
+        Ok(Self {
+            current,
+            proposed,
+            samples,
+            bounds,
+        })
+    }
+    pub fn proposed(&self) -> RestCurve {
+        self.proposed
+    }
+    /// ratatuiでレンダリングする文字列のリストを返す
+    /// * .0 => 現在と提案の曲線の比較
+    /// * .1 => キーヒント表示用文字列
+    pub fn render(&self) -> (Vec<Line<'static>>, Vec<Span<'static>>) {
+        let row = |label: &str, curve: &RestCurve| {
+            vec![
+                Line::from(label.to_string().bold()),
+                Line::from(format!(
+                    "L = {}  k = {:.5}  w0 = {}  誤差 = {}",
+                    curve.l.to_time_string(),
+                    curve.k,
+                    curve.w0.to_time_string(),
+                    time::Duration::from_secs_f64(curve.rms_error(&self.samples)).to_time_string()
+                )),
+                Line::from(format!("休憩時間: {}", curve.summary(&self.bounds))),
+            ]
+        };
+
+        let mut lines = vec![
+            Line::from("実際の休憩時間からのパラメーターの提案".white().bold()),
+            Line::from(format!(
+                "休憩モードの開始から本気モードを再開するまでの{}件の記録から求めました",
+                self.samples.len()
+            )),
+            Line::from(""),
+        ];
+        lines.extend(row("現在", &self.current));
+        lines.push(Line::from(""));
+        lines.extend(row("提案", &self.proposed));
+
+        (
+            lines,
+            vec![
+                " 提案を適用 ".into(),
+                "<Enter> ".blue().bold(),
+                " キャンセル ".into(),
+                "<Esc> ".blue().bold(),
+            ],
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curve::grid_curve as curve;
+    use crate::history::cycle;
+    use crate::timer::minutes;
+
+    fn session(profile: &str, workflow: WorkflowKind, works: &[u64]) -> SessionRecord {
+        let mut session = SessionRecord::new(profile, workflow);
+        session.cycles = works
+            .iter()
+            .map(|work| {
+                let rest = curve().rest(minutes(*work));
+                cycle(minutes(*work), rest, Some(rest))
+            })
+            .collect();
+        session
+    }
+
+    #[test]
+    fn uses_only_matching_samples() {
+        let mut sessions = vec![
+            session("default", WorkflowKind::Maji, &[10, 20, 30, 45]),
+            session("other", WorkflowKind::Maji, &[60, 90]),
+            session("default", WorkflowKind::Pomodoro, &[60, 90]),
+            session("default", WorkflowKind::Maji, &[60]),
+        ];
+        sessions[3].cycles[0].long_break = true;
+        let proposal = Proposal::new(&sessions, "default", curve(), RestBounds::default());
+        assert!(matches!(proposal, Err(LearnErr::NotEnoughSamples(4))));
+
+        // 休憩を取らずにセッションを終えたサイクルも使わない
+        sessions.push(session("default", WorkflowKind::Maji, &[120]));
+        sessions[4].cycles[0].taken = None;
+        sessions.push(session("default", WorkflowKind::Maji, &[60, 90]));
+        let proposal = Proposal::new(&sessions, "default", curve(), RestBounds::default())
+            .expect("十分な記録がある");
+        assert_eq!(proposal.samples.len(), 6);
+        assert!(proposal.proposed().rms_error(&proposal.samples) < 1.0);
+    }
+}
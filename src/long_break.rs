@@ -0,0 +1,106 @@
+use serde::{Deserialize, Serialize};
+use std::time;
+
+use super::curve::RestCurve;
+use super::timer::human_duration_option;
+
+/// 一定のサイクルごとに取る長い休憩
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct LongBreak {
+    /// このサイクル数ごとの休憩を長い休憩にする。省略した場合は長い休憩を取らない
+    every: Option<u32>,
+    /// 長い休憩の時間。作業時間に関わらずこの時間だけ休む
+    #[serde(with = "human_duration_option")]
+    fixed: Option<time::Duration>,
+    /// `fixed`を省略した場合に、長い休憩の時間を算出する曲線。省略した場合は通常の曲線を使う
+    curve: Option<RestCurve>,
+}
+
+/// 長い休憩の時間の決め方
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LongRest {
+    Fixed(time::Duration),
+    Curve(RestCurve),
+}
+
+impl LongBreak {
+    /// `cycle`番目 (1から数える) のサイクルの休憩が長い休憩であれば、その時間の決め方
+    /// * normal => 通常の休憩時間の曲線
+    pub fn rest(&self, cycle: u32, normal: RestCurve) -> Option<LongRest> {
+        let every = self.every.filter(|every| *every > 0)?;
+        if cycle == 0 || !cycle.is_multiple_of(every) {
+            return None;
+        }
+        Some(match self.fixed {
+            Some(fixed) => LongRest::Fixed(fixed),
+            None => LongRest::Curve(self.curve.unwrap_or(normal)),
+        })
+    }
+    /// * Err => (不正なフィールド名, 理由)
+    pub fn validate(&self) -> Result<(), (&'static str, String)> {
+        if self.every == Some(0) {
+            return Err(("every", "1以上の値を指定してください".to_string()));
+        }
+        if let Some(curve) = &self.curve {
+            if curve.k.is_nan() || curve.k <= 0.0 {
+                return Err((
+                    "curve",
+                    format!("`k`には0より大きい値を指定してください ({})", curve.k),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timer::minutes;
+
+    fn curve(l: u64) -> RestCurve {
+        RestCurve {
+            l: minutes(l),
+            k: 0.0017,
+            w0: minutes(40),
+        }
+    }
+
+    #[test]
+    fn long_rest_every_nth_cycle() {
+        let long_break = LongBreak {
+            every: Some(4),
+            fixed: Some(minutes(30)),
+            curve: None,
+        };
+        assert_eq!(long_break.rest(0, curve(40)), None);
+        assert_eq!(long_break.rest(3, curve(40)), None);
+        assert_eq!(
+            long_break.rest(8, curve(40)),
+            Some(LongRest::Fixed(minutes(30)))
+        );
+        assert_eq!(LongBreak::default().rest(4, curve(40)), None);
+    }
+
+    #[test]
+    fn long_rest_falls_back_to_curves() {
+        let own = LongBreak {
+            every: Some(2),
+            fixed: None,
+            curve: Some(curve(60)),
+        };
+        assert_eq!(own.rest(2, curve(40)), Some(LongRest::Curve(curve(60))));
+        let normal = LongBreak { curve: None, ..own };
+        assert_eq!(normal.rest(2, curve(40)), Some(LongRest::Curve(curve(40))));
+    }
+
+    #[test]
+    fn rejects_zero_every() {
+        let long_break = LongBreak {
+            every: Some(0),
+            ..LongBreak::default()
+        };
+        assert_eq!(long_break.validate().unwrap_err().0, "every");
+    }
+}
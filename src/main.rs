@@ -1,6 +1,24 @@
+mod adjust;
+mod bundle;
+mod cap;
+mod cli;
+mod curve;
+mod fatigue;
+mod history;
+mod learn;
+mod long_break;
+mod migration;
+mod milestone;
+mod presets;
+mod reload;
+mod review;
 mod settings;
+mod side;
 mod timer;
 mod tui;
+mod utility;
+mod wizard;
+mod workflow;
 
 use std::path;
 
@@ -15,7 +33,7 @@ use ratatui::{
     layout::{Alignment, Rect},
     style::Stylize,
     symbols::border,
-    text::{Line, Text},
+    text::{Line, Span, Text},
     widgets::{
         block::{Position, Title},
         Block, Paragraph, Widget,
@@ -26,14 +44,102 @@ use ratatui::{
 pub struct App {
     settings: settings::Settings,
     run: settings::RunData,
+    /// 設定の読み込み失敗などをユーザーに知らせるための警告
+    notice: Option<String>,
+    /// 一定時間だけ表示する通知と、その表示を始めた時刻
+    toast: Option<(String, std::time::Instant)>,
+    /// プロファイル選択中であれば、選択しているプロファイルの位置
+    profile_picker: Option<usize>,
+    /// プリセット選択中であれば、選択しているプリセットの位置
+    preset_picker: Option<usize>,
+    /// 休憩時間の曲線を決める質問の途中であれば、その状態
+    wizard: Option<wizard::Wizard>,
+    /// 記録から求めたパラメーターの提案を確認中であれば、その提案
+    proposal: Option<learn::Proposal>,
+    /// インポートする設定の確認中であれば、その差分
+    import_review: Option<review::ImportReview>,
+    /// バックアップから戻す設定の確認中であれば、その差分
+    restore_review: Option<review::RestoreReview>,
+    /// 設定ファイルの外部での変更の監視
+    watcher: Option<reload::ConfigWatcher>,
+    /// カウントダウンの時間を入力中であれば、その入力
+    countdown_input: Option<utility::DurationInput>,
+    /// メインのサイクルとは別に動作するタイマー
+    side: side::SidePanel,
+    /// サイドタイマーの名前と時間を入力中であれば、その入力
+    side_input: Option<side::SideTimerInput>,
+    /// 耐久モードで記録したラップの名前を入力中であれば、その入力
+    lap_input: Option<utility::LapLabelInput>,
     exit: bool,
 }
 
+/// 通知を表示し続ける時間
+const TOAST_DURATION: std::time::Duration = std::time::Duration::from_secs(5);
+/// 通知に表示する変更点の最大数
+const TOAST_MAX_CHANGES: usize = 3;
+
 impl App {
-    pub fn new() -> Self {
+    pub fn new(args: cli::Args) -> Self {
+        let override_errors = settings::init_overrides();
+        // 設定ファイルがまだ無ければ、テンプレートを作った後に質問で曲線を決める
+        let first_run = settings::config_path().is_ok_and(|path| !path.exists());
+        // 起動時の警告は上書きせず、発生した順に全て表示する
+        let mut notices: Vec<String> = Vec::new();
+        // 設定ファイルが壊れていても起動できるよう、デフォルト設定にフォールバックする
+        let mut settings = match settings::Settings::init() {
+            Ok(settings) => settings,
+            Err(err) => {
+                notices.push(format!(
+                    "設定ファイルを読み込めなかったため、デフォルト設定で起動しました: {}",
+                    err
+                ));
+                settings::Settings::fallback()
+            }
+        };
+        if let Some(profile) = &args.profile {
+            if let Err(err) = settings.set_active_profile(profile) {
+                notices.push(format!(
+                    "{}。{:?}で起動しました",
+                    err,
+                    settings.active_profile()
+                ));
+            }
+        }
+        if let Some(name) = &args.preset {
+            match presets::find(name) {
+                Some(preset) => {
+                    settings.set_timer(preset.timer());
+                    if let Err(err) = settings.save() {
+                        notices.push(format!("設定を保存できませんでした: {}", err));
+                    }
+                }
+                None => notices.push(format!("プリセット{:?}は存在しません", name)),
+            }
+        }
+        // 音声ファイルが見つからなくても設定は使えるため、警告だけを表示する
+        if let Some(warning) = settings.sound_warnings().first() {
+            notices.push(format!("音声ファイルが見つかりません: {}", warning));
+        }
+        if let Some(err) = override_errors.first() {
+            notices.push(format!("環境変数による上書きを無視しました: {}", err));
+        }
+        let notice = (!notices.is_empty()).then(|| notices.join("\n"));
         Self {
-            settings: settings::Settings::init().unwrap(),
+            settings,
             run: settings::RunData::new(),
+            notice,
+            toast: None,
+            profile_picker: None,
+            preset_picker: None,
+            wizard: (first_run || args.setup).then(wizard::Wizard::new),
+            proposal: None,
+            import_review: None,
+            restore_review: None,
+            watcher: settings::config_path().ok().map(reload::ConfigWatcher::new),
+            countdown_input: None,
+            side: side::SidePanel::new(),
+            side_input: None,
+            lap_input: None,
             exit: false,
         }
     }
@@ -42,7 +148,22 @@ impl App {
         while !self.exit {
             terminal.draw(|frame| self.render_frame(frame))?;
             self.handle_events()?;
-            self.run.state_process(&self.settings).unwrap();
+            if self.watcher.as_mut().is_some_and(|watcher| watcher.poll()) {
+                self.reload_settings();
+            }
+            let running = *self.run.mode() != TimerMode::Init;
+            let result = self.run.state_process(&self.settings);
+            self.report(result);
+            if running && *self.run.mode() == TimerMode::Init {
+                self.show_toast("一時停止が長く続いたため、セッションを終了しました".to_string());
+            }
+            match self.side.poll(&self.settings.other()) {
+                Ok(finished) if !finished.is_empty() => {
+                    self.show_toast(format!("{}の時間になりました", finished.join("、")));
+                }
+                Ok(_) => {}
+                Err(err) => self.show_toast(err.to_string()),
+            }
 
             // let file = std::fs::File::open("assets/finish.mp3").unwrap();
             // let (stream, stream_handle) = rodio::OutputStream::try_default().unwrap();
@@ -52,6 +173,10 @@ impl App {
             // audacity.set_volume(0.05);
             // std::thread::sleep(std::time::Duration::from_secs(10));
         }
+        // 終了時に実行中のセッションを履歴に残す
+        self.run
+            .finish_session()
+            .map_err(|err| io::Error::other(err.to_string()))?;
         Ok(())
     }
 
@@ -75,89 +200,492 @@ impl App {
     }
 
     fn handle_key_event(&mut self, key_event: KeyEvent) {
+        if self.wizard.is_some() {
+            self.handle_wizard_key_event(key_event);
+            return;
+        }
+        if self.profile_picker.is_some() {
+            self.handle_profile_picker_key_event(key_event);
+            return;
+        }
+        if self.restore_review.is_some() {
+            self.handle_restore_review_key_event(key_event);
+            return;
+        }
+        if self.import_review.is_some() {
+            self.handle_import_review_key_event(key_event);
+            return;
+        }
+        if self.preset_picker.is_some() {
+            self.handle_preset_picker_key_event(key_event);
+            return;
+        }
+        if self.proposal.is_some() {
+            self.handle_proposal_key_event(key_event);
+            return;
+        }
+        if self.countdown_input.is_some() {
+            self.handle_countdown_input_key_event(key_event);
+            return;
+        }
+        if self.side_input.is_some() {
+            self.handle_side_input_key_event(key_event);
+            return;
+        }
+        if self.lap_input.is_some() {
+            self.handle_lap_input_key_event(key_event);
+            return;
+        }
         match key_event.code {
             KeyCode::Char('q') | KeyCode::Char('Q') => self.exit(),
             KeyCode::Char(' ') => match self.run.mode() {
-                TimerMode::Init => self.run.init(&self.settings),
+                TimerMode::Init => {
+                    self.notice = None;
+                    let result = self.run.init(&self.settings);
+                    self.report(result);
+                }
                 _ => self.run.pause_or_resume(),
             },
             KeyCode::Char('i') | KeyCode::Char('I') => {
                 if let Ok(path) = settings::path_picker(false) {
-                    self.settings = settings::Settings::import(&path).unwrap();
-                    self.settings
-                        .export(
-                            &dirs::config_dir()
-                                .unwrap()
-                                .join("majitimer")
-                                .join("config.json"),
-                        )
-                        .unwrap();
-
-                    self.run.update(&self.settings);
+                    let imported = if bundle::is_bundle(&path) {
+                        bundle::import(&path)
+                            .map(|(imported, sounds)| (imported, Some(sounds)))
+                            .map_err(|err| err.to_string())
+                    } else {
+                        settings::Settings::import(&path)
+                            .map(|imported| (imported, None))
+                            .map_err(|err| err.to_string())
+                    };
+                    match imported {
+                        Ok((imported, sounds)) => {
+                            // すぐには反映せず、差分を確認してから取り込む
+                            let review =
+                                review::ImportReview::new(&self.settings, imported, sounds);
+                            if review.is_empty() {
+                                self.show_toast("現在の設定との差分はありません".to_string());
+                            } else {
+                                self.import_review = Some(review);
+                            }
+                        }
+                        Err(err) => {
+                            self.notice = Some(format!("設定をインポートできませんでした: {}", err))
+                        }
+                    }
                 }
             }
             KeyCode::Char('e') | KeyCode::Char('E') => {
                 if let Ok(path) = settings::path_picker(true) {
-                    self.settings.export(&path).unwrap();
+                    if let Err(err) = self.settings.export(&path) {
+                        self.notice = Some(format!("設定をエクスポートできませんでした: {}", err));
+                    }
+                }
+            }
+            KeyCode::Char('b') | KeyCode::Char('B') => {
+                if let Ok(path) = settings::bundle_path_picker() {
+                    match bundle::export(&self.settings, &path) {
+                        Ok(missing) if missing.is_empty() => {
+                            self.show_toast(format!("バンドルを保存しました: {:?}", path))
+                        }
+                        Ok(missing) => self.show_toast(format!(
+                            "バンドルを保存しました。見つからない音声ファイルは含めていません: {}",
+                            missing.join(", ")
+                        )),
+                        Err(err) => {
+                            self.notice = Some(format!("バンドルを保存できませんでした: {}", err))
+                        }
+                    }
+                }
+            }
+            KeyCode::Char('r') | KeyCode::Char('R') => {
+                let result = match self.run.mode() {
+                    TimerMode::Stopwatch | TimerMode::Countdown => self.run.restart_utility(),
+                    _ => self.run.init(&self.settings),
+                };
+                self.report(result);
+            }
+            KeyCode::Char('s') | KeyCode::Char('S') if *self.run.mode() == TimerMode::Init => {
+                self.notice = None;
+                let result = self.run.start_stopwatch();
+                self.report(result);
+            }
+            KeyCode::Char('c') | KeyCode::Char('C') if *self.run.mode() == TimerMode::Init => {
+                self.countdown_input = Some(utility::DurationInput::new());
+            }
+            KeyCode::Char('l') | KeyCode::Char('L') => {
+                let recorded = self.run.lap();
+                // ストップウォッチでは続けて記録できるよう、耐久モードでだけ名前を尋ねる
+                if recorded && *self.run.mode() == TimerMode::Endurance {
+                    self.lap_input = Some(utility::LapLabelInput::new());
+                }
+            }
+            KeyCode::Char('n') | KeyCode::Char('N') => {
+                self.side_input = Some(side::SideTimerInput::new())
+            }
+            KeyCode::Char(c @ ('+' | '=' | '-' | '>' | '<')) => {
+                let amount = self.settings.adjust().amount(matches!(c, '>' | '<'));
+                let decrease = matches!(c, '-' | '<');
+                if let Some(target) = self.run.adjust(amount, decrease) {
+                    self.show_toast(format!(
+                        "{}を{}{}",
+                        match target {
+                            history::AdjustTarget::Sprint => "本気モードの残り時間",
+                            history::AdjustTarget::Endurance => "耐久モードの経過時間",
+                            history::AdjustTarget::Rest => "休憩モードの残り時間",
+                            history::AdjustTarget::Remind => "リマインドまでの時間",
+                        },
+                        timer::format_duration(&amount),
+                        if decrease {
+                            "減らしました"
+                        } else {
+                            "増やしました"
+                        }
+                    ));
                 }
             }
-            KeyCode::Char('r') | KeyCode::Char('R') => self.run.init(&self.settings),
-            KeyCode::Char('m') | KeyCode::Char('M') => {
-                if let TimerMode::Endurance | TimerMode::UrgedToReMajiTime = self.run.mode() {
-                    self.run.mode_transition_start();
+            KeyCode::Char('d') | KeyCode::Char('D') => self.side.remove_selected(),
+            KeyCode::Tab => self.side.select_next(),
+            // すぐには戻さず、差分を確認してから戻す
+            KeyCode::Char('u') | KeyCode::Char('U') => match settings::Settings::latest_backup() {
+                Ok(backup) => {
+                    self.restore_review = Some(review::RestoreReview::new(&self.settings, backup))
+                }
+                Err(err) => self.show_toast(format!("前の設定に戻せませんでした: {}", err)),
+            },
+            KeyCode::Char('t') | KeyCode::Char('T') => self.preset_picker = Some(0),
+            KeyCode::Char('f') | KeyCode::Char('F') => {
+                let mut sessions = match history::load() {
+                    Ok(sessions) => sessions,
+                    Err(err) => {
+                        self.show_toast(err.to_string());
+                        return;
+                    }
+                };
+                sessions.extend(self.run.session().cloned());
+                match learn::Proposal::new(
+                    &sessions,
+                    self.settings.active_profile(),
+                    self.settings.profile_timer().curve(),
+                    self.settings.bounds().clone(),
+                ) {
+                    Ok(proposal) => self.proposal = Some(proposal),
+                    Err(err) => self.show_toast(err.to_string()),
                 }
             }
+            KeyCode::Char('p') | KeyCode::Char('P') => {
+                self.profile_picker = self
+                    .settings
+                    .profile_names()
+                    .position(|name| name == self.settings.active_profile());
+            }
+            KeyCode::Char('m') | KeyCode::Char('M') => match self.run.mode() {
+                TimerMode::Endurance | TimerMode::UrgedToReMajiTime => {
+                    self.run.mode_transition_start()
+                }
+                TimerMode::Stopwatch | TimerMode::Countdown => {
+                    let result = self.run.end_session();
+                    self.report(result);
+                }
+                _ => {}
+            },
             KeyCode::Char('1') => {
                 if let Ok(path) = settings::path_picker(false) {
-                    self.settings.other.set_finish_sound(&path);
-                    self.settings
-                        .export(
-                            &dirs::config_dir()
-                                .unwrap()
-                                .join("majitimer")
-                                .join("config.json"),
-                        )
-                        .unwrap();
+                    self.settings.other_mut().set_finish_sound(&path);
+                    self.save_settings();
                 }
             }
             KeyCode::Char('2') => {
                 if let Ok(path) = settings::path_picker(false) {
-                    self.settings.other.set_restart_sound(&path);
-                    self.settings
-                        .export(
-                            &dirs::config_dir()
-                                .unwrap()
-                                .join("majitimer")
-                                .join("config.json"),
-                        )
-                        .unwrap();
+                    self.settings.other_mut().set_restart_sound(&path);
+                    self.save_settings();
                 }
             }
             KeyCode::Char('3') => {
                 if let Ok(path) = settings::path_picker(false) {
-                    self.settings.other.set_remind_sound(&path);
-                    self.settings
-                        .export(
-                            &dirs::config_dir()
-                                .unwrap()
-                                .join("majitimer")
-                                .join("config.json"),
-                        )
-                        .unwrap();
-                }
-            }
-            // KeyCode::Char('4') => {
-            //     let file = std::fs::File::open("assets/finish.mp3").unwrap();
-            //     let (stream, stream_handle) = rodio::OutputStream::try_default().unwrap();
-            //     let audacity = stream_handle
-            //         .play_once(std::io::BufReader::new(file))
-            //         .unwrap();
-            //     audacity.set_volume(0.05);
-            // }
+                    self.settings.other_mut().set_remind_sound(&path);
+                    self.save_settings();
+                }
+            }
+            KeyCode::Char('4') => {
+                if let Ok(path) = settings::path_picker(false) {
+                    self.settings.other_mut().set_milestone_sound(&path);
+                    self.save_settings();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_profile_picker_key_event(&mut self, key_event: KeyEvent) {
+        let Some(selected) = self.profile_picker else {
+            return;
+        };
+        let count = self.settings.profile_names().count();
+        match key_event.code {
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.profile_picker = Some((selected + count - 1) % count)
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.profile_picker = Some((selected + 1) % count)
+            }
+            KeyCode::Enter => {
+                self.profile_picker = None;
+                let name = self
+                    .settings
+                    .profile_names()
+                    .nth(selected)
+                    .map(str::to_owned);
+                if let Some(name) = name {
+                    if let Err(err) = self.settings.set_active_profile(&name) {
+                        self.notice = Some(err.to_string());
+                        return;
+                    }
+                    self.save_settings();
+                    let result = self.run.update(&self.settings);
+                    self.report(result);
+                }
+            }
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('Q') => self.profile_picker = None,
+            _ => {}
+        }
+    }
+
+    fn handle_wizard_key_event(&mut self, key_event: KeyEvent) {
+        let Some(wizard) = &mut self.wizard else {
+            return;
+        };
+        match key_event.code {
+            KeyCode::Enter if wizard.is_finished() => {
+                let timer = wizard.timer(self.settings.profile_timer());
+                self.wizard = None;
+                self.settings.set_timer(timer);
+                self.save_settings();
+                let result = self.run.update(&self.settings);
+                self.report(result);
+                self.show_toast("回答に合わせて休憩時間の曲線を設定しました".to_string());
+            }
+            KeyCode::Enter => wizard.submit(),
+            KeyCode::Backspace if wizard.is_finished() => wizard.back(),
+            KeyCode::Backspace => wizard.backspace(),
+            KeyCode::Up => wizard.back(),
+            KeyCode::Char(c) => wizard.push(c),
+            KeyCode::Esc => self.wizard = None,
+            _ => {}
+        }
+    }
+
+    fn handle_countdown_input_key_event(&mut self, key_event: KeyEvent) {
+        let Some(input) = &mut self.countdown_input else {
+            return;
+        };
+        match key_event.code {
+            KeyCode::Enter => {
+                if let Some(duration) = input.submit() {
+                    self.countdown_input = None;
+                    self.notice = None;
+                    let result = self.run.start_countdown(duration);
+                    self.report(result);
+                }
+            }
+            KeyCode::Backspace => input.backspace(),
+            KeyCode::Char(c) => input.push(c),
+            KeyCode::Esc => self.countdown_input = None,
+            _ => {}
+        }
+    }
+
+    fn handle_side_input_key_event(&mut self, key_event: KeyEvent) {
+        let Some(input) = &mut self.side_input else {
+            return;
+        };
+        match key_event.code {
+            KeyCode::Enter => {
+                self.side.add(input.input());
+                self.side_input = None;
+            }
+            KeyCode::Backspace => input.backspace(),
+            KeyCode::Char(c) => input.push(c),
+            KeyCode::Esc => self.side_input = None,
+            _ => {}
+        }
+    }
+
+    fn handle_lap_input_key_event(&mut self, key_event: KeyEvent) {
+        let Some(input) = &mut self.lap_input else {
+            return;
+        };
+        match key_event.code {
+            KeyCode::Enter => {
+                self.run.label_last_lap(input.input());
+                self.lap_input = None;
+            }
+            KeyCode::Backspace => input.backspace(),
+            KeyCode::Char(c) => input.push(c),
+            KeyCode::Esc => self.lap_input = None,
+            _ => {}
+        }
+    }
+
+    fn handle_preset_picker_key_event(&mut self, key_event: KeyEvent) {
+        let Some(selected) = self.preset_picker else {
+            return;
+        };
+        let count = presets::PRESETS.len();
+        match key_event.code {
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.preset_picker = Some((selected + count - 1) % count)
+            }
+            KeyCode::Down | KeyCode::Char('j') => self.preset_picker = Some((selected + 1) % count),
+            KeyCode::Enter => {
+                self.preset_picker = None;
+                let preset = &presets::PRESETS[selected];
+                self.settings.set_timer(preset.timer());
+                self.save_settings();
+                let result = self.run.update(&self.settings);
+                self.report(result);
+                self.show_toast(format!(
+                    "プリセット{:?}を{:?}に適用しました",
+                    preset.name,
+                    self.settings.active_profile()
+                ));
+            }
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('Q') => self.preset_picker = None,
+            _ => {}
+        }
+    }
+
+    fn handle_proposal_key_event(&mut self, key_event: KeyEvent) {
+        let Some(proposal) = &self.proposal else {
+            return;
+        };
+        match key_event.code {
+            // 確認されるまでは設定を変えない
+            KeyCode::Enter => {
+                let timer = self
+                    .settings
+                    .profile_timer()
+                    .with_curve(proposal.proposed());
+                self.proposal = None;
+                self.settings.set_timer(timer);
+                self.save_settings();
+                let result = self.run.update(&self.settings);
+                self.report(result);
+                self.show_toast("記録から求めたパラメーターを適用しました".to_string());
+            }
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('Q') => self.proposal = None,
+            _ => {}
+        }
+    }
+
+    fn handle_import_review_key_event(&mut self, key_event: KeyEvent) {
+        let Some(review) = &mut self.import_review else {
+            return;
+        };
+        match key_event.code {
+            KeyCode::Up | KeyCode::Char('k') => review.select_previous(),
+            KeyCode::Down | KeyCode::Char('j') => review.select_next(),
+            KeyCode::Char(' ') => review.toggle(),
+            KeyCode::Char('a') | KeyCode::Char('A') => review.toggle_all(),
+            // 音声ファイルは取り込みを確定してから展開する
+            KeyCode::Enter => match review.apply(&self.settings) {
+                Ok(merged) => {
+                    self.import_review = None;
+                    self.settings = merged;
+                    self.save_settings();
+                    let result = self.run.update(&self.settings);
+                    self.report(result);
+                    self.show_toast("設定をインポートしました".to_string());
+                }
+                Err(err) => self.show_toast(format!("設定をインポートできませんでした: {}", err)),
+            },
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('Q') => self.import_review = None,
             _ => {}
         }
     }
 
+    fn handle_restore_review_key_event(&mut self, key_event: KeyEvent) {
+        match key_event.code {
+            KeyCode::Enter => {
+                self.restore_review = None;
+                match settings::Settings::restore_backup() {
+                    Ok((restored, remaining)) => {
+                        self.settings = restored;
+                        if let Some(watcher) = &mut self.watcher {
+                            watcher.acknowledge();
+                        }
+                        let result = self.run.update(&self.settings);
+                        self.report(result);
+                        self.show_toast(format!(
+                            "前の設定に戻しました (バックアップ: {}件)",
+                            remaining
+                        ));
+                    }
+                    Err(err) => self.show_toast(format!("前の設定に戻せませんでした: {}", err)),
+                }
+            }
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('Q') => self.restore_review = None,
+            _ => {}
+        }
+    }
+
+    /// 現在の設定を設定ファイルへ書き込み、失敗した場合は警告を表示する
+    fn save_settings(&mut self) {
+        if let Err(err) = self.settings.save() {
+            self.notice = Some(format!("設定を保存できませんでした: {}", err));
+        }
+        if let Some(watcher) = &mut self.watcher {
+            watcher.acknowledge();
+        }
+    }
+
+    /// 外部で変更された設定ファイルを読み込み直す
+    /// 不正な設定ファイルの場合は現在の設定を使い続ける
+    fn reload_settings(&mut self) {
+        let result = settings::config_path().and_then(|path| settings::Settings::import(&path));
+        match result {
+            Ok(reloaded) => {
+                let changes = self.settings.changes(&reloaded);
+                if changes.is_empty() {
+                    return;
+                }
+                self.settings = reloaded;
+                let result = self.run.update(&self.settings);
+                self.report(result);
+
+                let mut message = format!(
+                    "設定を再読み込みしました: {}",
+                    changes
+                        .iter()
+                        .take(TOAST_MAX_CHANGES)
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+                if changes.len() > TOAST_MAX_CHANGES {
+                    message += &format!(" 他{}件", changes.len() - TOAST_MAX_CHANGES);
+                }
+                if self.run.mode() != &TimerMode::Init {
+                    message += " (タイマーの値は次の本気モードから反映されます)";
+                }
+                self.show_toast(message);
+            }
+            Err(err) => {
+                self.show_toast(format!("設定ファイルの変更を反映できませんでした: {}", err))
+            }
+        }
+    }
+
+    fn show_toast(&mut self, message: String) {
+        self.toast = Some((message, std::time::Instant::now()));
+    }
+
+    /// タイマーの操作に失敗した場合に警告を表示する
+    fn report(&mut self, result: Result<(), settings::RunDataErr>) {
+        if let Err(err) = result {
+            self.notice = Some(err.to_string());
+        }
+    }
+
     fn exit(&mut self) {
         self.exit = true;
     }
@@ -166,8 +694,51 @@ impl App {
 impl Widget for &App {
     fn render(self, area: Rect, buf: &mut Buffer) {
         let mut run_text = self.run.render();
+        if let Some(selected) = self.profile_picker {
+            run_text = self.render_profile_picker(selected);
+        }
+        if let Some(selected) = self.preset_picker {
+            run_text = self.render_preset_picker(selected);
+        }
+        if let Some(proposal) = &self.proposal {
+            run_text = proposal.render();
+        }
+        if let Some(review) = &self.import_review {
+            run_text = review.render(&self.settings);
+        }
+        if let Some(review) = &self.restore_review {
+            run_text = review.render(&self.settings);
+        }
+        if let Some(wizard) = &self.wizard {
+            run_text = wizard.render(self.settings.profile_timer(), self.settings.bounds());
+        }
+        if let Some(input) = &self.countdown_input {
+            run_text = input.render();
+        }
+        if let Some(input) = &self.side_input {
+            run_text = input.render();
+        }
+        if let Some(input) = &self.lap_input {
+            run_text = input.render();
+        }
+        if !self.side.is_empty() && !self.overlay_open() {
+            run_text.0.extend(self.side.render());
+        }
         let block = {
-            let title = Title::from(" Maji Timer (本気タイマー) ".bold());
+            let title = Title::from(
+                match self.run.schedule() {
+                    Some(rule) => format!(
+                        " Maji Timer (本気タイマー) [{} / {}] ",
+                        self.settings.active_profile(),
+                        rule
+                    ),
+                    None => format!(
+                        " Maji Timer (本気タイマー) [{}] ",
+                        self.settings.active_profile()
+                    ),
+                }
+                .bold(),
+            );
             let instructions = Title::from(Line::from(run_text.1));
             Block::bordered()
                 .title(title.alignment(Alignment::Center))
@@ -179,9 +750,38 @@ impl Widget for &App {
                 .border_set(border::THICK)
         };
 
-        if self.run.paused() && self.run.mode() != &TimerMode::Init {
+        if self.run.paused() && self.run.mode() != &TimerMode::Init && !self.overlay_open() {
             run_text.0.push(Line::from("一時停止中".white().bold()))
         }
+        if self.run.mode() == &TimerMode::Init && !self.overlay_open() {
+            // どの設定ファイルを使っているか分かるよう、読み込み元を表示する
+            if let Ok((path, source)) = settings::config_location() {
+                run_text.0.push(Line::from(format!(
+                    "設定ファイル: {} ({})",
+                    path.display(),
+                    source
+                )));
+            }
+            let names = settings::overrides().names();
+            if !names.is_empty() {
+                run_text.0.push(Line::from(format!(
+                    "環境変数で上書き中: {}",
+                    names.join(", ")
+                )));
+            }
+        }
+        if let Some(notice) = &self.notice {
+            for line in notice.lines() {
+                run_text
+                    .0
+                    .push(Line::from(line.to_string().yellow().bold()))
+            }
+        }
+        if let Some((toast, shown_at)) = &self.toast {
+            if shown_at.elapsed() < TOAST_DURATION {
+                run_text.0.push(Line::from(toast.as_str().magenta()))
+            }
+        }
 
         let counter_text = Text::from(run_text.0);
 
@@ -192,12 +792,130 @@ impl Widget for &App {
     }
 }
 
+impl App {
+    /// タイマーの代わりに選択画面や確認画面を表示しているかどうか
+    fn overlay_open(&self) -> bool {
+        self.profile_picker.is_some()
+            || self.preset_picker.is_some()
+            || self.import_review.is_some()
+            || self.restore_review.is_some()
+            || self.countdown_input.is_some()
+            || self.side_input.is_some()
+            || self.lap_input.is_some()
+    }
+
+    fn render_preset_picker(&self, selected: usize) -> (Vec<Line<'static>>, Vec<Span<'static>>) {
+        let mut lines = vec![
+            Line::from("プリセットの選択".white().bold()),
+            Line::from(format!(
+                "現在: {}",
+                self.settings
+                    .timer()
+                    .curve()
+                    .summary(self.settings.bounds())
+            )),
+            Line::from(""),
+        ];
+        for (index, preset) in presets::PRESETS.iter().enumerate() {
+            let name = format!("{} - {}", preset.name, preset.description);
+            if index == selected {
+                lines.push(Line::from(format!("> {} <", name).black().on_white()));
+            } else {
+                lines.push(Line::from(name.bold()));
+            }
+            lines.push(Line::from(preset.curve().summary(self.settings.bounds())));
+        }
+        (
+            lines,
+            vec![
+                " 選択 ".into(),
+                "<↑/↓> ".blue().bold(),
+                " 適用 ".into(),
+                "<Enter> ".blue().bold(),
+                " キャンセル ".into(),
+                "<Esc> ".blue().bold(),
+            ],
+        )
+    }
+
+    fn render_profile_picker(&self, selected: usize) -> (Vec<Line<'static>>, Vec<Span<'static>>) {
+        let mut lines = vec![Line::from("プロファイルの選択".white().bold())];
+        for (index, name) in self.settings.profile_names().enumerate() {
+            if index == selected {
+                lines.push(Line::from(format!("> {} <", name).black().on_white()));
+            } else {
+                lines.push(Line::from(name.to_string()));
+            }
+        }
+        (
+            lines,
+            vec![
+                " 選択 ".into(),
+                "<↑/↓> ".blue().bold(),
+                " 決定 ".into(),
+                "<Enter> ".blue().bold(),
+                " キャンセル ".into(),
+                "<Esc> ".blue().bold(),
+            ],
+        )
+    }
+}
+
 pub fn main() -> io::Result<()> {
     // let settings = settings::Settings::template();
     // let export_path = settings::path_picker().unwrap();
     // settings.export(&export_path).unwrap();
+    let args = match cli::Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(cli::CliErr::Help) => {
+            println!("{}", cli::USAGE);
+            return Ok(());
+        }
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+    if args.list_presets || args.preview {
+        // 設定ファイルを作らずに読み込む。まだ無ければデフォルト設定を使う
+        let loaded = settings::config_path().and_then(|path| {
+            if path.exists() {
+                settings::Settings::import(&path)
+            } else {
+                Ok(settings::Settings::template())
+            }
+        });
+        let mut settings = match loaded {
+            Ok(settings) => settings,
+            Err(err) => {
+                eprintln!("設定ファイルを読み込めませんでした: {}", err);
+                std::process::exit(1);
+            }
+        };
+        if let Some(profile) = &args.profile {
+            if let Err(err) = settings.set_active_profile(profile) {
+                eprintln!("{}", err);
+                std::process::exit(2);
+            }
+        }
+        if args.list_presets {
+            println!("{}", presets::list(settings.bounds()));
+        }
+        if args.preview {
+            let curve = settings.timer().curve();
+            println!(
+                "プロファイル: {}\nL = {}  k = {:.5}  w0 = {}\n休憩時間: {}",
+                settings.active_profile(),
+                curve.l.to_time_string(),
+                curve.k,
+                curve.w0.to_time_string(),
+                curve.summary(settings.bounds())
+            );
+        }
+        return Ok(());
+    }
     let mut terminal = tui::init()?;
-    let app_result = App::new().run(&mut terminal);
+    let app_result = App::new(args).run(&mut terminal);
     tui::restore()?;
     app_result
 }
@@ -0,0 +1,222 @@
+use serde_json::Value;
+
+use super::settings::DEFAULT_PROFILE;
+use super::timer::format_duration;
+
+/// 現在の設定ファイルのスキーマバージョン
+pub const CURRENT_VERSION: u64 = 3;
+
+/// 1つ前のバージョンの設定を変換する関数
+type Migration = fn(&mut Value) -> Result<(), MigrationErr>;
+
+/// `MIGRATIONS[n]` はバージョン`n`の設定をバージョン`n + 1`へ変換する
+const MIGRATIONS: [Migration; CURRENT_VERSION as usize] = [v0_to_v1, v1_to_v2, v2_to_v3];
+
+#[derive(thiserror::Error, Debug)]
+pub enum MigrationErr {
+    #[error("設定ファイルの最上位がオブジェクトではありません")]
+    NotObject,
+    #[error("`version` の値が不正です")]
+    InvalidVersion,
+    #[error(
+        "バージョン{0}の設定ファイルには対応していません (対応バージョン: {CURRENT_VERSION}まで)"
+    )]
+    Unsupported(u64),
+    #[error("`{0}` の値が0以上の整数ではありません")]
+    InvalidField(String),
+    #[error("`{0}` の時間が長すぎます")]
+    TooLong(String),
+}
+
+/// 設定ファイルのバージョンを返す。`version`が無いものはバージョン0として扱う
+pub fn version_of(value: &Value) -> Result<u64, MigrationErr> {
+    let object = value.as_object().ok_or(MigrationErr::NotObject)?;
+    match object.get("version") {
+        None => Ok(0),
+        Some(version) => version.as_u64().ok_or(MigrationErr::InvalidVersion),
+    }
+}
+
+/// 古い設定を現在のバージョンまで順番にアップグレードする
+/// * 戻り値 => 移行前のバージョン (移行が不要だった場合は`None`)
+pub fn migrate(value: &mut Value) -> Result<Option<u64>, MigrationErr> {
+    let version = version_of(value)?;
+    if version > CURRENT_VERSION {
+        return Err(MigrationErr::Unsupported(version));
+    }
+    if version == CURRENT_VERSION {
+        return Ok(None);
+    }
+
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(value)?;
+    }
+    value["version"] = Value::from(CURRENT_VERSION);
+
+    Ok(Some(version))
+}
+
+/// v0: `version`の無い初期の形式。足りないフィールドはデフォルト値で補われるため、バージョンを付与するだけ
+fn v0_to_v1(value: &mut Value) -> Result<(), MigrationErr> {
+    value["version"] = Value::from(1);
+    Ok(())
+}
+
+/// v1: 時間を`*_min`と`*_sec`の組、`remind`を秒数で持つ形式。v2からは`"1m30s"`のような文字列で持つ
+fn v1_to_v2(value: &mut Value) -> Result<(), MigrationErr> {
+    if let Some(timer) = value.get_mut("timer").and_then(Value::as_object_mut) {
+        for name in ["majitime", "l", "w0"] {
+            let min_key = format!("{}_min", name);
+            let sec_key = format!("{}_sec", name);
+            let min = timer.remove(&min_key);
+            let sec = timer.remove(&sec_key);
+            if min.is_none() && sec.is_none() {
+                continue;
+            }
+            let min = field_u64(min, &min_key)?;
+            let sec = field_u64(sec, &sec_key)?;
+            let secs = min
+                .checked_mul(60)
+                .and_then(|secs| secs.checked_add(sec))
+                .ok_or_else(|| MigrationErr::TooLong(format!("timer.{}", name)))?;
+            timer.insert(
+                name.to_string(),
+                Value::from(format_duration(&std::time::Duration::from_secs(secs))),
+            );
+        }
+    }
+    if let Some(remind) = value.pointer_mut("/other/remind") {
+        let secs = field_u64(Some(remind.take()), "other.remind")?;
+        *remind = Value::from(format_duration(&std::time::Duration::from_secs(secs)));
+    }
+    value["version"] = Value::from(2);
+    Ok(())
+}
+
+/// v1の数値の項目を0以上の整数として読む。無い項目は0として扱う
+fn field_u64(value: Option<Value>, field: &str) -> Result<u64, MigrationErr> {
+    match value {
+        None => Ok(0),
+        Some(value) => value
+            .as_u64()
+            .ok_or_else(|| MigrationErr::InvalidField(field.to_string())),
+    }
+}
+
+/// v2: `timer`と`other`を最上位に1組だけ持つ形式。v3からは名前付きのプロファイルとして複数持つ
+/// `version`が無いだけで既にプロファイルを持つ設定は、そのプロファイルを残す
+fn v2_to_v3(value: &mut Value) -> Result<(), MigrationErr> {
+    if let Some(object) = value.as_object_mut() {
+        if !object.contains_key("profiles") {
+            let mut profile = serde_json::Map::new();
+            for section in ["timer", "other"] {
+                if let Some(section_value) = object.remove(section) {
+                    profile.insert(section.to_string(), section_value);
+                }
+            }
+            let mut profiles = serde_json::Map::new();
+            profiles.insert(DEFAULT_PROFILE.to_string(), Value::Object(profile));
+            object.insert("profiles".to_string(), Value::Object(profiles));
+        }
+        object
+            .entry("active")
+            .or_insert_with(|| Value::from(DEFAULT_PROFILE));
+    }
+    value["version"] = Value::from(3);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn migrates_v0_to_current() {
+        let mut value = json!({
+            "timer": { "majitime_min": 0, "majitime_sec": 30, "l_min": 40, "k": 0.0017, "w0_min": 40 },
+            "other": { "remind": 90, "finish_sound": "" }
+        });
+        assert_eq!(migrate(&mut value).unwrap(), Some(0));
+        assert_eq!(
+            value,
+            json!({
+                "version": CURRENT_VERSION,
+                "active": DEFAULT_PROFILE,
+                "profiles": {
+                    DEFAULT_PROFILE: {
+                        "timer": { "majitime": "30s", "l": "40m", "k": 0.0017, "w0": "40m" },
+                        "other": { "remind": "1m30s", "finish_sound": "" }
+                    }
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn migrates_v2_to_v3() {
+        let mut value = json!({ "version": 2, "timer": { "majitime": "1m" } });
+        assert_eq!(migrate(&mut value).unwrap(), Some(2));
+        assert_eq!(
+            value["profiles"][DEFAULT_PROFILE]["timer"]["majitime"],
+            "1m"
+        );
+        assert!(value.get("timer").is_none());
+    }
+
+    #[test]
+    fn keeps_profiles_without_version() {
+        let mut value = json!({
+            "active": "work",
+            "profiles": { "work": { "timer": { "majitime": "2m" } } }
+        });
+        migrate(&mut value).unwrap();
+        assert_eq!(value["active"], "work");
+        assert_eq!(value["profiles"]["work"]["timer"]["majitime"], "2m");
+        assert!(value["profiles"].get(DEFAULT_PROFILE).is_none());
+    }
+
+    #[test]
+    fn rejects_invalid_v1_values() {
+        for timer in [
+            json!({ "majitime_min": "1" }),
+            json!({ "l_sec": -5 }),
+            json!({ "w0_min": 1.5 }),
+        ] {
+            let mut value = json!({ "timer": timer });
+            assert!(matches!(
+                migrate(&mut value),
+                Err(MigrationErr::InvalidField(_))
+            ));
+        }
+        let mut value = json!({ "other": { "remind": "90" } });
+        assert!(matches!(
+            migrate(&mut value),
+            Err(MigrationErr::InvalidField(field)) if field == "other.remind"
+        ));
+    }
+
+    #[test]
+    fn rejects_overflowing_v1_durations() {
+        let mut value = json!({ "timer": { "majitime_min": u64::MAX, "majitime_sec": 0 } });
+        assert!(matches!(
+            migrate(&mut value),
+            Err(MigrationErr::TooLong(field)) if field == "timer.majitime"
+        ));
+        let mut value = json!({ "timer": { "l_min": u64::MAX / 60, "l_sec": 60 } });
+        assert!(matches!(
+            migrate(&mut value),
+            Err(MigrationErr::TooLong(field)) if field == "timer.l"
+        ));
+    }
+
+    #[test]
+    fn current_version_is_untouched() {
+        let mut value = json!({ "version": CURRENT_VERSION, "active": "a", "profiles": {} });
+        assert_eq!(migrate(&mut value).unwrap(), None);
+        assert!(matches!(
+            migrate(&mut json!({ "version": CURRENT_VERSION + 1 })),
+            Err(MigrationErr::Unsupported(_))
+        ));
+    }
+}
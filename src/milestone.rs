@@ -0,0 +1,145 @@
+use serde::{Deserialize, Serialize};
+use std::time;
+
+use super::timer::{format_duration, human_duration_option};
+
+/// 耐久モード中に音と画面の点滅で知らせる節目
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Milestones {
+    /// 耐久モードの経過時間がこの時間の倍数に達するたびに知らせる。省略した場合は知らせない
+    #[serde(with = "human_duration_option")]
+    every: Option<time::Duration>,
+    /// 今休憩に入った場合の休憩時間が、休憩時間の最大値`l`に対してこの割合に達したら知らせる (`0.5`で半分)
+    rest_ratios: Vec<f64>,
+}
+
+/// 耐久モードの間に達した節目の数
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Progress {
+    intervals: u32,
+    ratios: usize,
+}
+
+impl Milestones {
+    /// 新たに節目に達していれば、その説明を返す
+    /// * elapsed => 耐久モードの経過時間
+    /// * rest => 今休憩に入った場合の休憩時間
+    /// * l => 休憩時間の最大値
+    pub fn check(
+        &self,
+        progress: &mut Progress,
+        elapsed: time::Duration,
+        rest: time::Duration,
+        l: time::Duration,
+    ) -> Option<String> {
+        let mut reached = None;
+        if let Some(every) = self.every.filter(|every| !every.is_zero()) {
+            let intervals = (elapsed.as_secs_f64() / every.as_secs_f64()) as u32;
+            if intervals > progress.intervals {
+                progress.intervals = intervals;
+                reached = Some(format!(
+                    "{}経過しました",
+                    format_duration(&(every * intervals))
+                ));
+            }
+        }
+
+        let mut ratios = self.rest_ratios.clone();
+        ratios.sort_by(f64::total_cmp);
+        let passed = ratios
+            .iter()
+            .take_while(|ratio| rest.as_secs_f64() >= l.as_secs_f64() * **ratio)
+            .count();
+        if passed > progress.ratios {
+            progress.ratios = passed;
+            reached = Some(format!(
+                "休憩時間が最大値の{}%に達しました",
+                (ratios[passed - 1] * 100.0).round()
+            ));
+        }
+        reached
+    }
+    /// * Err => (不正なフィールド名, 理由)
+    pub fn validate(&self) -> Result<(), (&'static str, String)> {
+        if self.every.is_some_and(|every| every.is_zero()) {
+            return Err(("every", "0秒より長い時間を指定してください".to_string()));
+        }
+        if let Some(ratio) = self
+            .rest_ratios
+            .iter()
+            .find(|ratio| ratio.is_nan() || **ratio <= 0.0)
+        {
+            return Err((
+                "rest_ratios",
+                format!("0より大きい値を指定してください ({})", ratio),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timer::minutes;
+
+    #[test]
+    fn every_interval_is_reported_once() {
+        let milestones = Milestones {
+            every: Some(minutes(25)),
+            rest_ratios: Vec::new(),
+        };
+        let mut progress = Progress::default();
+        let check = |progress: &mut Progress, elapsed| {
+            milestones.check(progress, elapsed, time::Duration::ZERO, minutes(40))
+        };
+        assert_eq!(check(&mut progress, minutes(24)), None);
+        assert_eq!(
+            check(&mut progress, minutes(25)),
+            Some("25m経過しました".to_string())
+        );
+        assert_eq!(check(&mut progress, minutes(26)), None);
+        assert_eq!(
+            check(&mut progress, minutes(50)),
+            Some("50m経過しました".to_string())
+        );
+    }
+
+    #[test]
+    fn rest_ratios_are_reported_in_order() {
+        let milestones = Milestones {
+            every: None,
+            rest_ratios: vec![0.75, 0.5],
+        };
+        let mut progress = Progress::default();
+        let check = |progress: &mut Progress, rest| {
+            milestones.check(progress, minutes(60), rest, minutes(40))
+        };
+        assert_eq!(check(&mut progress, minutes(19)), None);
+        assert_eq!(
+            check(&mut progress, minutes(20)),
+            Some("休憩時間が最大値の50%に達しました".to_string())
+        );
+        assert_eq!(check(&mut progress, minutes(21)), None);
+        assert_eq!(
+            check(&mut progress, minutes(30)),
+            Some("休憩時間が最大値の75%に達しました".to_string())
+        );
+        assert_eq!(check(&mut progress, minutes(40)), None);
+    }
+
+    #[test]
+    fn invalid_milestones_are_rejected() {
+        let zero = Milestones {
+            every: Some(time::Duration::ZERO),
+            rest_ratios: Vec::new(),
+        };
+        assert!(zero.validate().is_err());
+        let negative = Milestones {
+            every: None,
+            rest_ratios: vec![-0.5],
+        };
+        assert!(negative.validate().is_err());
+    }
+}
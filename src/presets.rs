@@ -0,0 +1,106 @@
+use std::time;
+
+use super::curve::{RestBounds, RestCurve};
+use super::settings::Timer;
+
+/// 休憩時間の曲線と本気モードの時間の組み合わせの雛形
+#[derive(Debug)]
+pub struct Preset {
+    pub name: &'static str,
+    pub description: &'static str,
+    majitime: time::Duration,
+    l: time::Duration,
+    k: f64,
+    w0: time::Duration,
+}
+
+pub const PRESETS: [Preset; 4] = [
+    Preset {
+        name: "gentle",
+        description: "短い作業でも休憩しやすい緩やかな曲線",
+        majitime: time::Duration::from_secs(30),
+        l: time::Duration::from_secs(30 * 60),
+        k: 0.001,
+        w0: time::Duration::from_secs(30 * 60),
+    },
+    Preset {
+        name: "standard",
+        description: "初期設定と同じ標準的な曲線",
+        majitime: time::Duration::from_secs(30),
+        l: time::Duration::from_secs(40 * 60),
+        k: 0.0017,
+        w0: time::Duration::from_secs(40 * 60),
+    },
+    Preset {
+        name: "sprint",
+        description: "長く作業したときだけ休憩が増える急な曲線",
+        majitime: time::Duration::from_secs(2 * 60),
+        l: time::Duration::from_secs(20 * 60),
+        k: 0.003,
+        w0: time::Duration::from_secs(50 * 60),
+    },
+    Preset {
+        name: "pomodoro",
+        description: "25分の本気モードの後に約5分休憩するポモドーロ風",
+        majitime: time::Duration::from_secs(25 * 60),
+        l: time::Duration::from_secs(15 * 60),
+        k: 0.0015,
+        w0: time::Duration::from_secs(8 * 60),
+    },
+];
+
+impl Preset {
+    pub fn timer(&self) -> Timer {
+        Timer::new(self.majitime, self.l, self.k, self.w0)
+    }
+    pub fn curve(&self) -> RestCurve {
+        self.timer().curve()
+    }
+}
+
+pub fn find(name: &str) -> Option<&'static Preset> {
+    PRESETS
+        .iter()
+        .find(|preset| preset.name.eq_ignore_ascii_case(name))
+}
+
+/// `--list-presets`で表示するプリセットの一覧
+pub fn list(bounds: &RestBounds) -> String {
+    PRESETS
+        .iter()
+        .map(|preset| {
+            format!(
+                "{:<10} {}\n           休憩時間: {}",
+                preset.name,
+                preset.description,
+                preset.curve().summary(bounds)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timer::minutes;
+
+    #[test]
+    fn find_ignores_case() {
+        assert_eq!(find("Pomodoro").map(|preset| preset.name), Some("pomodoro"));
+        assert!(find("unknown").is_none());
+    }
+
+    #[test]
+    fn presets_are_distinct_and_increasing() {
+        for (index, preset) in PRESETS.iter().enumerate() {
+            assert!(PRESETS[..index]
+                .iter()
+                .all(|other| other.name != preset.name));
+            let curve = preset.curve();
+            assert!(curve.rest(minutes(15)) < curve.rest(minutes(120)));
+        }
+        let list = list(&RestBounds::default());
+        assert!(PRESETS.iter().all(|preset| list.contains(preset.name)));
+    }
+}
@@ -0,0 +1,48 @@
+use std::path;
+use std::time;
+
+/// 設定ファイルを確認する間隔
+const CHECK_INTERVAL: time::Duration = time::Duration::from_secs(1);
+
+/// 設定ファイルの更新日時を定期的に確認し、外部での変更を検知する
+pub struct ConfigWatcher {
+    path: path::PathBuf,
+    modified: Option<time::SystemTime>,
+    last_check: time::Instant,
+}
+
+impl ConfigWatcher {
+    pub fn new(path: path::PathBuf) -> Self {
+        let modified = modified(&path);
+        Self {
+            path,
+            modified,
+            last_check: time::Instant::now(),
+        }
+    }
+    /// 前回の確認以降に設定ファイルが更新されていれば`true`を返す
+    pub fn poll(&mut self) -> bool {
+        if self.last_check.elapsed() < CHECK_INTERVAL {
+            return false;
+        }
+        self.last_check = time::Instant::now();
+
+        let modified = modified(&self.path);
+        if modified == self.modified {
+            return false;
+        }
+        self.modified = modified;
+        // 削除された場合は反映するものが無いので、変更として扱わない
+        modified.is_some()
+    }
+    /// アプリケーション自身が設定ファイルを書き込んだ後に呼び、その書き込みを変更として検知しないようにする
+    pub fn acknowledge(&mut self) {
+        self.modified = modified(&self.path);
+    }
+}
+
+fn modified(path: &path::Path) -> Option<time::SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
@@ -0,0 +1,214 @@
+use ratatui::style::Stylize;
+use ratatui::text::{Line, Span};
+
+use super::bundle::StagedSounds;
+use super::settings::{Change, Settings, SettingsErr};
+
+/// インポートする設定と現在の設定の差分を確認し、取り込むセクションを選ぶための状態
+pub struct ImportReview {
+    incoming: Settings,
+    changes: Vec<Change>,
+    /// (セクション, 取り込むかどうか)
+    sections: Vec<(String, bool)>,
+    selected: usize,
+    /// バンドルからインポートする場合は、確定したときに展開する音声ファイル
+    sounds: Option<StagedSounds>,
+}
+
+impl ImportReview {
+    pub fn new(current: &Settings, incoming: Settings, sounds: Option<StagedSounds>) -> Self {
+        let changes = current.changes(&incoming);
+        let mut sections: Vec<(String, bool)> = Vec::new();
+        for change in &changes {
+            let section = change.section();
+            if !sections.iter().any(|(name, _)| name == &section) {
+                sections.push((section, true));
+            }
+        }
+        Self {
+            incoming,
+            changes,
+            sections,
+            selected: 0,
+            sounds,
+        }
+    }
+    /// 現在の設定と差分が無ければ`true`
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+    pub fn select_previous(&mut self) {
+        let count = self.sections.len();
+        self.selected = (self.selected + count - 1) % count;
+    }
+    pub fn select_next(&mut self) {
+        self.selected = (self.selected + 1) % self.sections.len();
+    }
+    /// 選択しているセクションを取り込むかどうかを切り替える
+    pub fn toggle(&mut self) {
+        if let Some((_, included)) = self.sections.get_mut(self.selected) {
+            *included = !*included;
+        }
+    }
+    /// 全てのセクションを取り込むか、全て取り込まないかを切り替える
+    pub fn toggle_all(&mut self) {
+        let include = !self.sections.iter().all(|(_, included)| *included);
+        for (_, included) in &mut self.sections {
+            *included = include;
+        }
+    }
+    fn included_sections(&self) -> Vec<String> {
+        self.sections
+            .iter()
+            .filter(|(_, included)| *included)
+            .map(|(section, _)| section.clone())
+            .collect()
+    }
+    /// 選択したセクションを現在の設定に取り込んだ設定
+    pub fn merged(&self, current: &Settings) -> Result<Settings, SettingsErr> {
+        current.merge(&self.incoming, &self.included_sections())
+    }
+    /// 取り込みを確定する。バンドルからのインポートでは、選択したセクションが参照する音声ファイルを展開する
+    pub fn apply(&self, current: &Settings) -> Result<Settings, String> {
+        // 音声ファイルを書き込む前に、取り込める組み合わせかを確かめる
+        let merged = self.merged(current).map_err(|err| err.to_string())?;
+        let Some(sounds) = &self.sounds else {
+            return Ok(merged);
+        };
+        let sections = self.included_sections();
+        let mut incoming = self.incoming.clone();
+        sounds
+            .install(&mut incoming, &sections)
+            .map_err(|err| err.to_string())?;
+        current
+            .merge(&incoming, &sections)
+            .map_err(|err| err.to_string())
+    }
+    /// ratatuiでレンダリングする文字列のリストを返す
+    /// * .0 => セクションごとの差分と休憩時間の変化
+    /// * .1 => キーヒント表示用文字列
+    pub fn render(&self, current: &Settings) -> (Vec<Line<'static>>, Vec<Span<'static>>) {
+        let mut lines = vec![
+            Line::from("設定のインポート".white().bold()),
+            Line::from(format!(
+                "{:<32}{:>16} │ {:<16}",
+                "項目", "現在", "インポート"
+            )),
+        ];
+        for (index, (section, included)) in self.sections.iter().enumerate() {
+            let mark = if *included { "[x]" } else { "[ ]" };
+            let header = format!("{} {}", mark, section);
+            if index == self.selected {
+                lines.push(Line::from(header.black().on_white()));
+            } else {
+                lines.push(Line::from(header.bold()));
+            }
+            for change in self.changes.iter().filter(|c| &c.section() == section) {
+                let field = change
+                    .field
+                    .strip_prefix(section.as_str())
+                    .map(|field| field.trim_start_matches('.'))
+                    .filter(|field| !field.is_empty())
+                    .unwrap_or(&change.field);
+                lines.push(Line::from(format!(
+                    "{:<32}{:>16} │ {:<16}",
+                    field,
+                    change.before.as_deref().unwrap_or("-"),
+                    change.after.as_deref().unwrap_or("-"),
+                )));
+            }
+        }
+
+        lines.push(Line::from(""));
+        lines.push(Line::from(format!(
+            "休憩時間 (現在):     {}",
+            current.timer().curve().summary(current.bounds())
+        )));
+        match self.merged(current) {
+            Ok(merged) => lines.push(Line::from(format!(
+                "休憩時間 (取り込み後): {}",
+                merged.timer().curve().summary(merged.bounds())
+            ))),
+            Err(err) => lines.push(Line::from(
+                format!("この組み合わせでは取り込めません: {}", err).yellow(),
+            )),
+        }
+
+        (
+            lines,
+            vec![
+                " 選択 ".into(),
+                "<↑/↓> ".blue().bold(),
+                " 取り込む/取り込まない ".into(),
+                "<Space> ".blue().bold(),
+                " 全て ".into(),
+                "<A> ".blue().bold(),
+                " インポート ".into(),
+                "<Enter> ".blue().bold(),
+                " キャンセル ".into(),
+                "<Esc> ".blue().bold(),
+            ],
+        )
+    }
+}
+
+/// バックアップから設定を戻す前に、現在の設定との差分を確認するための状態
+pub struct RestoreReview {
+    backup: Settings,
+    changes: Vec<Change>,
+}
+
+impl RestoreReview {
+    pub fn new(current: &Settings, backup: Settings) -> Self {
+        Self {
+            changes: current.changes(&backup),
+            backup,
+        }
+    }
+    /// ratatuiでレンダリングする文字列のリストを返す
+    /// * .0 => 戻すと変わる項目と休憩時間の変化
+    /// * .1 => キーヒント表示用文字列
+    pub fn render(&self, current: &Settings) -> (Vec<Line<'static>>, Vec<Span<'static>>) {
+        let mut lines = vec![
+            Line::from("前の設定に戻す".white().bold()),
+            Line::from(format!(
+                "{:<32}{:>16} │ {:<16}",
+                "項目", "現在", "バックアップ"
+            )),
+        ];
+        if self.changes.is_empty() {
+            lines.push(Line::from("現在の設定との差分はありません"));
+        }
+        for change in &self.changes {
+            lines.push(Line::from(format!(
+                "{:<32}{:>16} │ {:<16}",
+                change.field,
+                change.before.as_deref().unwrap_or("-"),
+                change.after.as_deref().unwrap_or("-"),
+            )));
+        }
+
+        lines.push(Line::from(""));
+        lines.push(Line::from(format!(
+            "休憩時間 (現在):     {}",
+            current.timer().curve().summary(current.bounds())
+        )));
+        lines.push(Line::from(format!(
+            "休憩時間 (戻した後): {}",
+            self.backup.timer().curve().summary(self.backup.bounds())
+        )));
+        lines.push(Line::from(
+            "戻す前の設定はバックアップとして残ります".dark_gray(),
+        ));
+
+        (
+            lines,
+            vec![
+                " 戻す ".into(),
+                "<Enter> ".blue().bold(),
+                " キャンセル ".into(),
+                "<Esc> ".blue().bold(),
+            ],
+        )
+    }
+}
@@ -1,8 +1,18 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::io::Write;
 use std::path;
 
-use super::timer::MyToType;
+use super::adjust::Adjust;
+use super::cap::{self, CapEvent, EnduranceCap};
+use super::curve::{RestBounds, RestCurve};
+use super::fatigue::{DayTotals, Fatigue};
+use super::history;
+use super::long_break::{LongBreak, LongRest};
+use super::migration;
+use super::milestone::{self, Milestones};
+use super::timer::{human_duration, human_duration_option, MyToType};
+use super::workflow::{Step, WorkflowKind};
 
 use ratatui::style::Stylize;
 use ratatui::text::Line;
@@ -10,37 +20,179 @@ use ratatui::text::Span;
 
 use rodio::{source::Source, Decoder, OutputStream};
 
-#[derive(Serialize, Deserialize, Debug)]
-struct Timer {
-    majitime_min: usize,
-    majitime_sec: usize,
-    l_min: usize,
-    l_sec: usize,
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Timer {
+    #[serde(with = "human_duration")]
+    majitime: std::time::Duration,
+    #[serde(with = "human_duration")]
+    l: std::time::Duration,
     k: f64,
-    w0_min: usize,
-    w0_sec: usize,
+    #[serde(with = "human_duration")]
+    w0: std::time::Duration,
 }
 
 impl Timer {
     fn template() -> Self {
         Self {
-            majitime_min: 0,
-            majitime_sec: 30,
-            l_min: 40,
-            l_sec: 0,
+            majitime: std::time::Duration::from_secs(30),
+            l: std::time::Duration::from_secs(40 * 60),
             k: 0.0017,
-            w0_min: 40,
-            w0_sec: 0,
+            w0: std::time::Duration::from_secs(40 * 60),
+        }
+    }
+}
+
+impl Default for Timer {
+    fn default() -> Self {
+        Self::template()
+    }
+}
+
+impl Timer {
+    pub fn new(
+        majitime: std::time::Duration,
+        l: std::time::Duration,
+        k: f64,
+        w0: std::time::Duration,
+    ) -> Self {
+        Self { majitime, l, k, w0 }
+    }
+    /// 本気モードの時間はそのままに、休憩時間の曲線を置き換えた`Timer`
+    pub fn with_curve(&self, curve: RestCurve) -> Self {
+        Self {
+            l: curve.l,
+            k: curve.k,
+            w0: curve.w0,
+            ..self.clone()
+        }
+    }
+    /// 休憩時間の算出に使う曲線
+    pub fn curve(&self) -> RestCurve {
+        RestCurve {
+            l: self.l,
+            k: self.k,
+            w0: self.w0,
+        }
+    }
+    /// * Err => (不正なフィールド名, 理由)
+    fn validate(&self) -> Result<(), (&'static str, String)> {
+        if self.k.is_nan() || self.k <= 0.0 {
+            return Err(("k", format!("0より大きい値を指定してください ({})", self.k)));
+        }
+        if self.l.is_zero() {
+            return Err(("l", "0秒より長い時間を指定してください".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// 曜日と時間帯によって使用する`Timer`を切り替える規則
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScheduleRule {
+    /// 画面に表示する規則の名前
+    name: String,
+    /// 適用する曜日 (`"sat"`, `"sun"`など)。空の場合は毎日
+    #[serde(default)]
+    days: Vec<chrono::Weekday>,
+    /// 適用を開始する時刻 (`"18:00"`)。省略した場合は0時から
+    #[serde(default, with = "clock_time")]
+    from: Option<chrono::NaiveTime>,
+    /// 適用を終了する時刻。省略した場合は24時まで。`from`より前の時刻の場合は日をまたぐ
+    #[serde(default, with = "clock_time")]
+    to: Option<chrono::NaiveTime>,
+    /// 適用する時間のパラメーター。省略したフィールドはプロファイルの`timer`の値になる
+    #[serde(default)]
+    timer: TimerOverride,
+}
+
+/// `ScheduleRule`で`Timer`の一部のフィールドだけを上書きするためのパラメーター
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct TimerOverride {
+    #[serde(
+        with = "human_duration_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    majitime: Option<std::time::Duration>,
+    #[serde(
+        with = "human_duration_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    l: Option<std::time::Duration>,
+    k: Option<f64>,
+    #[serde(
+        with = "human_duration_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    w0: Option<std::time::Duration>,
+}
+
+impl TimerOverride {
+    /// `base`のうち、指定されたフィールドだけを置き換えた`Timer`
+    fn apply(&self, base: &Timer) -> Timer {
+        Timer {
+            majitime: self.majitime.unwrap_or(base.majitime),
+            l: self.l.unwrap_or(base.l),
+            k: self.k.unwrap_or(base.k),
+            w0: self.w0.unwrap_or(base.w0),
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+impl ScheduleRule {
+    fn matches(&self, now: &chrono::NaiveDateTime) -> bool {
+        use chrono::Datelike;
+
+        if !self.days.is_empty() && !self.days.contains(&now.weekday()) {
+            return false;
+        }
+        let time = now.time();
+        match (self.from, self.to) {
+            (None, None) => true,
+            (Some(from), None) => from <= time,
+            (None, Some(to)) => time < to,
+            (Some(from), Some(to)) if from <= to => from <= time && time < to,
+            (Some(from), Some(to)) => from <= time || time < to,
+        }
+    }
+}
+
+/// `ScheduleRule`の時刻を`"18:00"`のような文字列として読み書きするためのモジュール
+mod clock_time {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    const FORMAT: &str = "%H:%M";
+
+    pub fn serialize<S: Serializer>(
+        time: &Option<chrono::NaiveTime>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match time {
+            Some(time) => serializer.serialize_str(&time.format(FORMAT).to_string()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<chrono::NaiveTime>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|time| chrono::NaiveTime::parse_from_str(&time, FORMAT).map_err(de::Error::custom))
+            .transpose()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
 pub struct Other {
-    pub remind: usize,
+    #[serde(with = "human_duration")]
+    pub remind: std::time::Duration,
     finish_sound: String,
     restart_sound: String,
     remind_sound: String,
+    /// 耐久モードの節目に達したときに再生されるサウンド
+    milestone_sound: String,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -52,10 +204,11 @@ pub enum GetPathErr {
 impl Other {
     fn template() -> Self {
         Self {
-            remind: 10,
+            remind: std::time::Duration::from_secs(10),
             finish_sound: String::new(),
             restart_sound: String::new(),
             remind_sound: String::new(),
+            milestone_sound: String::new(),
         }
     }
     pub fn set_finish_sound(&mut self, finish_sound_path: &path::Path) {
@@ -82,6 +235,18 @@ impl Other {
         //     .to_string_lossy()
         //     .into_owned();
     }
+    pub fn set_milestone_sound(&mut self, milestone_sound_path: &path::Path) {
+        self.milestone_sound = milestone_sound_path.to_string_lossy().into_owned();
+    }
+    /// 設定されている音声ファイルのパス (finish, restart, remind, milestoneの順)
+    pub fn sounds_mut(&mut self) -> [&mut String; 4] {
+        [
+            &mut self.finish_sound,
+            &mut self.restart_sound,
+            &mut self.remind_sound,
+            &mut self.milestone_sound,
+        ]
+    }
     pub fn get_finish_sound(&self) -> Result<&str, GetPathErr> {
         if &self.finish_sound == "" {
             Err(GetPathErr::NoPath)
@@ -103,6 +268,13 @@ impl Other {
             Ok(&self.remind_sound)
         }
     }
+    pub fn get_milestone_sound(&self) -> Result<&str, GetPathErr> {
+        if self.milestone_sound.is_empty() {
+            Err(GetPathErr::NoPath)
+        } else {
+            Ok(&self.milestone_sound)
+        }
+    }
     // pub fn get_finish_sound_print(&self) -> Result<&str, GetPathErr> {
     //     if &self.finish_sound_print == "" {
     //         Err(GetPathErr::NoPath)
@@ -126,10 +298,145 @@ impl Other {
     // }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Settings {
+impl Default for Other {
+    fn default() -> Self {
+        Self::template()
+    }
+}
+
+/// 休憩時間の算出に使う作業時間の数え方
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Accounting {
+    /// 本気モードの時間も作業時間に含める
+    include_majitime: bool,
+    /// 一時停止していた時間を作業時間から除く
+    exclude_pauses: bool,
+    /// 一時停止がこの時間を超えたらセッションを終了する。省略した場合は終了しない
+    #[serde(with = "human_duration_option")]
+    max_pause: Option<std::time::Duration>,
+}
+
+impl Default for Accounting {
+    fn default() -> Self {
+        Self {
+            include_majitime: false,
+            exclude_pauses: true,
+            max_pause: None,
+        }
+    }
+}
+
+impl Accounting {
+    /// * Err => (不正なフィールド名, 理由)
+    fn validate(&self) -> Result<(), (&'static str, String)> {
+        if self.max_pause.is_some_and(|max_pause| max_pause.is_zero()) {
+            return Err(("max_pause", "0秒より長い時間を指定してください".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// 名前を付けて切り替えられる設定の組
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Profile {
+    /// 作業と休憩のサイクルの進め方
+    pub workflow: WorkflowKind,
     pub timer: Timer,
     pub other: Other,
+    /// 上から順に調べ、最初に当てはまった規則の`timer`を`timer`の代わりに使う
+    pub schedule: Vec<ScheduleRule>,
+    /// その日の作業の累計に応じて休憩時間を延ばす項
+    pub fatigue: Fatigue,
+    /// 休憩時間の算出に使う作業時間の数え方
+    pub accounting: Accounting,
+    /// 算出した休憩時間に適用する下限・上限と丸め
+    pub bounds: RestBounds,
+    /// 耐久モード中に知らせる節目
+    pub milestones: Milestones,
+    /// 耐久モードの時間の上限
+    pub cap: EnduranceCap,
+    /// 一定のサイクルごとに取る長い休憩
+    pub long_break: LongBreak,
+    /// 実行中のタイマーを手動で増減する幅
+    pub adjust: Adjust,
+}
+
+impl Profile {
+    /// * Err => (不正なフィールド名, 理由)
+    fn validate(&self) -> Result<(), (String, String)> {
+        self.timer
+            .validate()
+            .map_err(|(field, reason)| (format!("timer.{}", field), reason))?;
+        for (index, rule) in self.schedule.iter().enumerate() {
+            rule.timer
+                .apply(&self.timer)
+                .validate()
+                .map_err(|(field, reason)| {
+                    (format!("schedule[{}].timer.{}", index, field), reason)
+                })?;
+        }
+        self.fatigue
+            .validate()
+            .map_err(|(field, reason)| (format!("fatigue.{}", field), reason))?;
+        self.accounting
+            .validate()
+            .map_err(|(field, reason)| (format!("accounting.{}", field), reason))?;
+        self.bounds
+            .validate()
+            .map_err(|(field, reason)| (format!("bounds.{}", field), reason))?;
+        self.milestones
+            .validate()
+            .map_err(|(field, reason)| (format!("milestones.{}", field), reason))?;
+        self.cap
+            .validate()
+            .map_err(|(field, reason)| (format!("cap.{}", field), reason))?;
+        self.long_break
+            .validate()
+            .map_err(|(field, reason)| (format!("long_break.{}", field), reason))?;
+        self.adjust
+            .validate()
+            .map_err(|(field, reason)| (format!("adjust.{}", field), reason))?;
+        if self.other.remind.is_zero() {
+            return Err((
+                "other.remind".to_string(),
+                "0秒より長い時間を指定してください".to_string(),
+            ));
+        }
+        Ok(())
+    }
+    /// 設定されているが存在しない音声ファイル
+    /// * .0 => フィールド名
+    /// * .1 => 設定されているパス
+    fn missing_sounds(&self) -> Vec<(&'static str, &str)> {
+        [
+            ("other.finish_sound", &self.other.finish_sound),
+            ("other.restart_sound", &self.other.restart_sound),
+            ("other.remind_sound", &self.other.remind_sound),
+            ("other.milestone_sound", &self.other.milestone_sound),
+        ]
+        .into_iter()
+        .filter(|(_, sound)| !sound.is_empty() && !resolve_path(sound).exists())
+        .map(|(field, sound)| (field, sound.as_str()))
+        .collect()
+    }
+}
+
+/// 最初に作られるプロファイルの名前
+pub const DEFAULT_PROFILE: &str = "default";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Settings {
+    /// 設定ファイルのスキーマバージョン。`migration`モジュールを参照
+    version: u64,
+    /// 使用中のプロファイルの名前
+    active: String,
+    profiles: BTreeMap<String, Profile>,
+    /// 設定ファイルを読み込めずにデフォルト設定で起動した場合は`true`
+    /// 元の設定ファイルを上書きしないよう、`save`で保存しない
+    #[serde(skip)]
+    fallback: bool,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -138,53 +445,706 @@ pub enum SettingsErr {
     Io(#[from] std::io::Error),
     #[error("serde_jsonの書き込みに失敗しました")]
     SerdeWrite(#[from] serde_json::Error),
-    #[error("serde_jsonの読み込みに失敗しました")]
-    SerdeRead,
+    #[error("{}`{field}` の読み込みに失敗しました: {message}", location(*.line, *.column))]
+    SerdeRead {
+        line: usize,
+        column: usize,
+        field: String,
+        message: String,
+    },
+    #[error("`{field}` の値が不正です: {reason}")]
+    Invalid { field: String, reason: String },
+    #[error("プロファイル{0:?}は存在しません")]
+    NoProfile(String),
+    #[error("設定ファイルの移行に失敗しました: {0}")]
+    Migration(#[from] migration::MigrationErr),
     #[error("{0:?}は存在しません")]
     NoFile(std::ffi::OsString),
     #[error("保存をキャンセルしました")]
     SaveCanceled,
+    #[error(
+        "設定ファイルを読み込めずにデフォルト設定で起動しているため、設定ファイルには保存しません"
+    )]
+    Fallback,
+}
+
+/// エラー位置の表示。移行後の値から読み込んだ場合など、位置が分からないときは空文字列
+fn location(line: usize, column: usize) -> String {
+    if line == 0 {
+        String::new()
+    } else {
+        format!("{}行{}列目 ", line, column)
+    }
+}
+
+impl From<serde_path_to_error::Error<serde_json::Error>> for SettingsErr {
+    fn from(err: serde_path_to_error::Error<serde_json::Error>) -> Self {
+        let field = err.path().to_string();
+        let inner = err.into_inner();
+        SettingsErr::SerdeRead {
+            line: inner.line(),
+            column: inner.column(),
+            field,
+            message: inner.to_string(),
+        }
+    }
 }
 
 impl Settings {
     pub fn template() -> Self {
         Self {
-            timer: Timer::template(),
-            other: Other::template(),
+            version: migration::CURRENT_VERSION,
+            active: DEFAULT_PROFILE.to_string(),
+            profiles: BTreeMap::from([(DEFAULT_PROFILE.to_string(), Profile::default())]),
+            fallback: false,
+        }
+    }
+    /// 設定ファイルを読み込めなかったときに使うデフォルト設定。設定ファイルには保存されない
+    pub fn fallback() -> Self {
+        Self {
+            fallback: true,
+            ..Self::template()
+        }
+    }
+    /// 全てのプロファイルで、設定されているが存在しない音声ファイルの警告
+    pub fn sound_warnings(&self) -> Vec<String> {
+        self.profiles
+            .iter()
+            .flat_map(|(name, profile)| {
+                profile
+                    .missing_sounds()
+                    .into_iter()
+                    .map(move |(field, sound)| {
+                        format!("`profiles.{}.{}` の{:?}は存在しません", name, field, sound)
+                    })
+            })
+            .collect()
+    }
+    /// 使用中のプロファイルの名前
+    pub fn active_profile(&self) -> &str {
+        &self.active
+    }
+    /// 設定されているプロファイルの名前の一覧
+    pub fn profile_names(&self) -> impl Iterator<Item = &str> {
+        self.profiles.keys().map(String::as_str)
+    }
+    /// 使用するプロファイルを切り替える
+    pub fn set_active_profile(&mut self, name: &str) -> Result<(), SettingsErr> {
+        if !self.profiles.contains_key(name) {
+            return Err(SettingsErr::NoProfile(name.to_string()));
+        }
+        self.active = name.to_string();
+        Ok(())
+    }
+    fn profile(&self) -> &Profile {
+        // `active`が存在することは`validate`と`set_active_profile`で保証している
+        &self.profiles[&self.active]
+    }
+    /// 使用中のプロファイルの`Timer`。環境変数による上書きを反映する
+    pub fn timer(&self) -> Timer {
+        overrides().apply_timer(&self.profile().timer)
+    }
+    /// `now`の時点で使用する`Timer`と、当てはまった規則の名前。環境変数による上書きを反映する
+    pub fn scheduled_timer(&self, now: &chrono::NaiveDateTime) -> (Timer, Option<&str>) {
+        let profile = self.profile();
+        match profile.schedule.iter().find(|rule| rule.matches(now)) {
+            Some(rule) => (
+                overrides().apply_timer(&rule.timer.apply(&profile.timer)),
+                Some(&rule.name),
+            ),
+            None => (overrides().apply_timer(&profile.timer), None),
         }
     }
+    /// 使用中のプロファイルの`Other`。環境変数による上書きを反映する
+    pub fn other(&self) -> Other {
+        overrides().apply_other(&self.profile().other)
+    }
+    /// 使用中のプロファイルの疲労の項
+    pub fn fatigue(&self) -> &Fatigue {
+        &self.profile().fatigue
+    }
+    /// 使用中のプロファイルの作業時間の数え方
+    pub fn accounting(&self) -> &Accounting {
+        &self.profile().accounting
+    }
+    /// 使用中のプロファイルの休憩時間の下限・上限と丸め
+    pub fn bounds(&self) -> &RestBounds {
+        &self.profile().bounds
+    }
+    /// 使用中のプロファイルの耐久モードの節目
+    pub fn milestones(&self) -> &Milestones {
+        &self.profile().milestones
+    }
+    /// 使用中のプロファイルの耐久モードの時間の上限
+    pub fn cap(&self) -> &EnduranceCap {
+        &self.profile().cap
+    }
+    /// 使用中のプロファイルの手法
+    pub fn workflow(&self) -> WorkflowKind {
+        self.profile().workflow
+    }
+    /// 使用中のプロファイルの長い休憩
+    pub fn long_break(&self) -> &LongBreak {
+        &self.profile().long_break
+    }
+    /// 使用中のプロファイルのタイマーを手動で増減する幅
+    pub fn adjust(&self) -> &Adjust {
+        &self.profile().adjust
+    }
+    /// 使用中のプロファイルに保存されている`Timer`。環境変数による上書きを含まない
+    pub fn profile_timer(&self) -> &Timer {
+        &self.profile().timer
+    }
+    /// 使用中のプロファイルの`Timer`を置き換える
+    pub fn set_timer(&mut self, timer: Timer) {
+        self.profiles
+            .get_mut(&self.active)
+            .expect("active profile exists")
+            .timer = timer;
+    }
+    /// 全てのプロファイルの (プロファイル名, そのプロファイルの`Other`)
+    pub fn others_mut(&mut self) -> impl Iterator<Item = (&str, &mut Other)> {
+        self.profiles
+            .iter_mut()
+            .map(|(name, profile)| (name.as_str(), &mut profile.other))
+    }
+    pub fn other_mut(&mut self) -> &mut Other {
+        &mut self
+            .profiles
+            .get_mut(&self.active)
+            .expect("active profile exists")
+            .other
+    }
     pub fn init() -> Result<Self, SettingsErr> {
-        let input = dirs::config_dir().unwrap()
-            .join("majitimer")
-            .join("config.json");
+        let input = config_path()?;
         if input.exists() {
-            Ok(Self::import(&input).unwrap())
+            let (settings, migrated_from) = Self::load(&input)?;
+            // 古い形式の設定ファイルは元のファイルを残したうえで新しい形式に書き換える
+            if let Some(version) = migrated_from {
+                let mut backup = input.clone().into_os_string();
+                backup.push(format!(".v{}.bak", version));
+                std::fs::copy(&input, &backup)?;
+                settings.export(&input)?;
+            }
+            Ok(settings)
         } else {
             let template = Self::template();
-            template.export(&input).unwrap();
+            template.export(&input)?;
             Ok(template)
         }
     }
     pub fn import(path: &path::Path) -> Result<Self, SettingsErr> {
-        let input = std::fs::read_to_string(path).unwrap();
-        // 指定したパスのjsonファイルが存在する場合はそこから読み込む。
-        if let Ok(deserialized) = serde_json::from_str(&input) {
-            return Ok(deserialized);
-        } else {
-            return Err(SettingsErr::SerdeRead);
+        Ok(Self::load(path)?.0)
+    }
+    /// 設定ファイルを読み込み、必要であれば現在のバージョンへ移行する
+    /// * .1 => 移行前のバージョン (移行しなかった場合は`None`)
+    fn load(path: &path::Path) -> Result<(Self, Option<u64>), SettingsErr> {
+        if !path.exists() {
+            return Err(SettingsErr::NoFile(path.as_os_str().to_owned()));
         }
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+    /// JSON文字列から設定を読み込み、必要であれば現在のバージョンへ移行する
+    /// * .1 => 移行前のバージョン (移行しなかった場合は`None`)
+    pub fn from_json(input: &str) -> Result<(Self, Option<u64>), SettingsErr> {
+        let mut value: serde_json::Value =
+            serde_path_to_error::deserialize(&mut serde_json::Deserializer::from_str(input))?;
+        let migrated_from = migration::migrate(&mut value)?;
+
+        // どのフィールドで失敗したかを報告できるよう、パスを追跡しながら読み込む
+        // 移行していなければ、行と列を報告できるよう元の文字列から読み込む
+        let deserialized: Self = match migrated_from {
+            None => {
+                serde_path_to_error::deserialize(&mut serde_json::Deserializer::from_str(input))?
+            }
+            Some(_) => serde_path_to_error::deserialize(value)?,
+        };
+        deserialized.validate()?;
+        Ok((deserialized, migrated_from))
+    }
+    /// 読み込んだ値がタイマーとして動作可能かを検証する
+    pub fn validate(&self) -> Result<(), SettingsErr> {
+        if !self.profiles.contains_key(&self.active) {
+            return Err(SettingsErr::NoProfile(self.active.clone()));
+        }
+        for (name, profile) in &self.profiles {
+            profile
+                .validate()
+                .map_err(|(field, reason)| SettingsErr::Invalid {
+                    field: format!("profiles.{}.{}", name, field),
+                    reason,
+                })?;
+        }
+        Ok(())
+    }
+    /// `self`から`other`への変更点を項目ごとに列挙する
+    pub fn changes(&self, other: &Self) -> Vec<Change> {
+        let mut before = BTreeMap::new();
+        let mut after = BTreeMap::new();
+        if let (Ok(self_value), Ok(other_value)) =
+            (serde_json::to_value(self), serde_json::to_value(other))
+        {
+            flatten(String::new(), self_value, &mut before);
+            flatten(String::new(), other_value, &mut after);
+        }
+
+        let fields: std::collections::BTreeSet<&String> =
+            before.keys().chain(after.keys()).collect();
+        fields
+            .into_iter()
+            .filter(|field| before.get(*field) != after.get(*field))
+            .map(|field| Change {
+                field: field.clone(),
+                before: before.get(field).cloned(),
+                after: after.get(field).cloned(),
+            })
+            .collect()
+    }
+    /// `incoming`のうち`sections`に含まれるセクションだけを取り込んだ設定を返す
+    /// セクションは`Change::section`の形式で指定する
+    pub fn merge(&self, incoming: &Self, sections: &[String]) -> Result<Self, SettingsErr> {
+        let mut merged = serde_json::to_value(self)?;
+        let incoming = serde_json::to_value(incoming)?;
+        for section in sections {
+            let keys: Vec<&str> = section.split('.').collect();
+            let Some((last, parents)) = keys.split_last() else {
+                continue;
+            };
+            let source = keys
+                .iter()
+                .try_fold(&incoming, |value, key| value.get(*key));
+
+            // 現在の設定に無いプロファイルを取り込む場合は、空のオブジェクトから作る
+            let mut target = Some(&mut merged);
+            for key in parents {
+                target = target
+                    .and_then(serde_json::Value::as_object_mut)
+                    .map(|object| {
+                        object
+                            .entry(key.to_string())
+                            .or_insert_with(|| serde_json::json!({}))
+                    });
+            }
+            match (source, target.and_then(serde_json::Value::as_object_mut)) {
+                (Some(source), Some(target)) => {
+                    target.insert(last.to_string(), source.clone());
+                }
+                (None, Some(target)) => {
+                    target.remove(*last);
+                }
+                _ => {}
+            }
+        }
+        // 全てのセクションが取り除かれたプロファイルは、プロファイルごと取り除く
+        if let Some(profiles) = merged["profiles"].as_object_mut() {
+            profiles.retain(|_, profile| profile.as_object().is_none_or(|p| !p.is_empty()));
+        }
+        // 使用するプロファイルだけを取り込み、そのプロファイルを取り込まなかった場合は、現在のプロファイルを使い続ける
+        let active_missing = merged["active"]
+            .as_str()
+            .is_some_and(|active| merged["profiles"].get(active).is_none());
+        if active_missing {
+            merged["active"] = serde_json::Value::String(self.active.clone());
+        }
+
+        let mut merged: Self = serde_path_to_error::deserialize(merged)?;
+        merged.validate()?;
+        // デフォルト設定に取り込んだ場合も、取り込まなかったセクションで元の設定ファイルを上書きしない
+        merged.fallback = self.fallback;
+        Ok(merged)
     }
     pub fn export(&self, path: &path::Path) -> Result<(), SettingsErr> {
-        let parent_dir = path.parent().unwrap();
-        if !parent_dir.exists() {
-            std::fs::create_dir_all(parent_dir).unwrap();
+        if let Some(parent_dir) = path.parent() {
+            if !parent_dir.exists() {
+                std::fs::create_dir_all(parent_dir)?;
+            }
         }
-        let mut file = std::fs::File::create(&path).expect(&format!("{:?}", path));
-        file.write_all(serde_json::to_string_pretty(&self).unwrap().as_bytes()).unwrap();
+        // 書き込み中に中断されても元のファイルが壊れないよう、一時ファイルに書き込んでから置き換える
+        let mut temp_path = path.as_os_str().to_owned();
+        temp_path.push(".tmp");
+        let mut file = std::fs::File::create(&temp_path)?;
+        file.write_all(serde_json::to_string_pretty(&self)?.as_bytes())?;
+        file.sync_all()?;
+        std::fs::rename(&temp_path, path)?;
 
         Ok(())
     }
+    /// 設定ファイルに保存する。それまでの設定ファイルはバックアップとして残す
+    pub fn save(&self) -> Result<(), SettingsErr> {
+        if self.fallback {
+            return Err(SettingsErr::Fallback);
+        }
+        let path = config_path()?;
+        rotate_backups(&path)?;
+        self.export(&path)
+    }
+    /// 最も新しいバックアップの設定を読み込む
+    pub fn latest_backup() -> Result<Self, SettingsErr> {
+        let latest = backup_path(&config_path()?, 1);
+        if !latest.exists() {
+            return Err(SettingsErr::NoFile(latest.into_os_string()));
+        }
+        Self::import(&latest)
+    }
+    /// 最も新しいバックアップを設定ファイルに戻し、戻した設定を返す
+    /// 戻す前の設定ファイルは最も新しいバックアップとして残す
+    /// * .1 => 残っているバックアップの数
+    pub fn restore_backup() -> Result<(Self, usize), SettingsErr> {
+        let path = config_path()?;
+        let restored = Self::latest_backup()?;
+
+        // 戻したバックアップを取り除き、残りを1つずつ新しい側へずらしてから、現在の設定を残す
+        std::fs::remove_file(backup_path(&path, 1))?;
+        for n in 2..=BACKUP_COUNT {
+            let backup = backup_path(&path, n);
+            if backup.exists() {
+                std::fs::rename(&backup, backup_path(&path, n - 1))?;
+            }
+        }
+        rotate_backups(&path)?;
+        restored.export(&path)?;
+
+        let remaining = (1..=BACKUP_COUNT)
+            .filter(|n| backup_path(&path, *n).exists())
+            .count();
+        Ok((restored, remaining))
+    }
+}
+
+/// 設定ファイルのバックアップを残す世代数
+const BACKUP_COUNT: usize = 5;
+
+/// `n`世代前の設定ファイルのバックアップのパス (`config.json.1.bak`が最も新しい)
+fn backup_path(path: &path::Path, n: usize) -> path::PathBuf {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(format!(".{}.bak", n));
+    backup.into()
+}
+
+/// バックアップを1世代ずつ古い側へずらし、現在の設定ファイルを最も新しいバックアップにする
+fn rotate_backups(path: &path::Path) -> Result<(), SettingsErr> {
+    if !path.exists() {
+        return Ok(());
+    }
+    let oldest = backup_path(path, BACKUP_COUNT);
+    if oldest.exists() {
+        std::fs::remove_file(oldest)?;
+    }
+    for n in (1..BACKUP_COUNT).rev() {
+        let backup = backup_path(path, n);
+        if backup.exists() {
+            std::fs::rename(&backup, backup_path(path, n + 1))?;
+        }
+    }
+    std::fs::copy(path, backup_path(path, 1))?;
+    Ok(())
+}
+/// 設定ファイルの場所をどのように決めたか
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigSource {
+    /// 環境変数`MAJITIMER_CONFIG`
+    Env,
+    /// 実行ファイルと同じディレクトリの`config.json` (ポータブルモード)
+    Portable,
+    /// 環境変数`XDG_CONFIG_HOME`
+    Xdg,
+    /// OSごとの標準の設定ディレクトリ
+    Default,
+}
+
+impl std::fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigSource::Env => write!(f, "環境変数 {}", CONFIG_ENV),
+            ConfigSource::Portable => write!(f, "ポータブルモード"),
+            ConfigSource::Xdg => write!(f, "環境変数 XDG_CONFIG_HOME"),
+            ConfigSource::Default => write!(f, "標準の設定ディレクトリ"),
+        }
+    }
+}
+
+/// 設定ファイルのパスを指定する環境変数
+pub const CONFIG_ENV: &str = "MAJITIMER_CONFIG";
+
+/// 設定ファイルのパスと、そのパスに決めた理由
+/// 優先順位は`MAJITIMER_CONFIG`、実行ファイルの隣の`config.json`、`XDG_CONFIG_HOME`、標準の設定ディレクトリの順
+pub fn config_location() -> Result<(path::PathBuf, ConfigSource), SettingsErr> {
+    if let Some(path) = std::env::var_os(CONFIG_ENV).filter(|path| !path.is_empty()) {
+        return Ok((path.into(), ConfigSource::Env));
+    }
+    if let Some(portable) = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.join("config.json")))
+        .filter(|portable| portable.exists())
+    {
+        return Ok((portable, ConfigSource::Portable));
+    }
+    if let Some(xdg) = std::env::var_os("XDG_CONFIG_HOME").filter(|xdg| !xdg.is_empty()) {
+        return Ok((
+            path::PathBuf::from(xdg)
+                .join("majitimer")
+                .join("config.json"),
+            ConfigSource::Xdg,
+        ));
+    }
+    let dir = dirs::config_dir().ok_or(SettingsErr::NoFile("config_dir".into()))?;
+    Ok((
+        dir.join("majitimer").join("config.json"),
+        ConfigSource::Default,
+    ))
+}
+
+/// 起動時に読み込み、変更を保存する設定ファイルのパス
+pub fn config_path() -> Result<path::PathBuf, SettingsErr> {
+    Ok(config_location()?.0)
+}
+
+/// 設定ファイルや履歴ファイルを置くディレクトリ
+pub fn config_dir() -> Option<path::PathBuf> {
+    config_path()
+        .ok()
+        .and_then(|path| path.parent().map(path::Path::to_path_buf))
+}
+
+/// 設定ファイル内の相対パスを、設定ディレクトリを基準としたパスに変換する
+pub fn resolve_path(path: &str) -> path::PathBuf {
+    let path = path::Path::new(path);
+    match config_dir() {
+        Some(dir) if path.is_relative() => dir.join(path),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// 設定ディレクトリを一時ディレクトリに切り替えて`test`を実行する
+/// 環境変数はテスト全体で共有されるため、同時に1つだけ実行する
+#[cfg(test)]
+pub fn with_config_dir(name: &str, history: &[u8], test: impl FnOnce()) {
+    static LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    let _guard = LOCK.lock().unwrap_or_else(|err| err.into_inner());
+    let dir = std::env::temp_dir().join(format!("majitimer-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("history.jsonl"), history).unwrap();
+    std::env::set_var(CONFIG_ENV, dir.join("config.json"));
+
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(test));
+    std::env::remove_var(CONFIG_ENV);
+    std::fs::remove_dir_all(&dir).unwrap();
+    if let Err(panic) = result {
+        std::panic::resume_unwind(panic);
+    }
+}
+
+/// 環境変数による設定値の上書き。設定ファイルには保存されない
+#[derive(Debug, Default)]
+pub struct Overrides {
+    majitime: Option<std::time::Duration>,
+    l: Option<std::time::Duration>,
+    k: Option<f64>,
+    w0: Option<std::time::Duration>,
+    remind: Option<std::time::Duration>,
+    finish_sound: Option<String>,
+    restart_sound: Option<String>,
+    remind_sound: Option<String>,
+    milestone_sound: Option<String>,
+    /// 値を読み込めた環境変数の名前
+    names: Vec<&'static str>,
+}
+
+static OVERRIDES: std::sync::OnceLock<Overrides> = std::sync::OnceLock::new();
+
+/// 環境変数による上書き。初めて呼ばれたときに環境変数を読み込む
+pub fn overrides() -> &'static Overrides {
+    OVERRIDES.get_or_init(|| Overrides::from_env().0)
+}
+
+/// 環境変数による上書きを読み込み、読み込めなかった環境変数のエラーを返す
+pub fn init_overrides() -> Vec<SettingsErr> {
+    let (overrides, errors) = Overrides::from_env();
+    let _ = OVERRIDES.set(overrides);
+    errors
+}
+
+/// 設定値を上書きできる環境変数
+const OVERRIDE_ENVS: [&str; 9] = [
+    "MAJITIMER_TIMER_MAJITIME",
+    "MAJITIMER_TIMER_L",
+    "MAJITIMER_TIMER_K",
+    "MAJITIMER_TIMER_W0",
+    "MAJITIMER_OTHER_REMIND",
+    "MAJITIMER_OTHER_FINISH_SOUND",
+    "MAJITIMER_OTHER_RESTART_SOUND",
+    "MAJITIMER_OTHER_REMIND_SOUND",
+    "MAJITIMER_OTHER_MILESTONE_SOUND",
+];
+
+impl Overrides {
+    fn from_env() -> (Self, Vec<SettingsErr>) {
+        let mut overrides = Self::default();
+        let mut errors = Vec::new();
+        for name in OVERRIDE_ENVS {
+            let Some(value) = std::env::var(name).ok().filter(|value| !value.is_empty()) else {
+                continue;
+            };
+            match overrides.set(name, &value) {
+                Ok(()) => overrides.names.push(name),
+                Err(reason) => errors.push(SettingsErr::Invalid {
+                    field: name.to_string(),
+                    reason,
+                }),
+            }
+        }
+        (overrides, errors)
+    }
+    /// * Err => 値が不正な理由
+    fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let duration = || super::timer::parse_duration(value).map_err(|err| err.to_string());
+        let positive = |duration: std::time::Duration| {
+            if duration.is_zero() {
+                Err("0秒より長い時間を指定してください".to_string())
+            } else {
+                Ok(duration)
+            }
+        };
+        let sound = || {
+            if resolve_path(value).exists() {
+                Ok(value.to_string())
+            } else {
+                Err(format!("{:?}は存在しません", value))
+            }
+        };
+        match name {
+            "MAJITIMER_TIMER_MAJITIME" => self.majitime = Some(duration()?),
+            "MAJITIMER_TIMER_L" => self.l = Some(positive(duration()?)?),
+            "MAJITIMER_TIMER_K" => {
+                let k: f64 = value
+                    .parse()
+                    .map_err(|_| format!("{:?}は数値ではありません", value))?;
+                if k.is_nan() || k <= 0.0 {
+                    return Err(format!("0より大きい値を指定してください ({})", k));
+                }
+                self.k = Some(k);
+            }
+            "MAJITIMER_TIMER_W0" => self.w0 = Some(duration()?),
+            "MAJITIMER_OTHER_REMIND" => self.remind = Some(positive(duration()?)?),
+            "MAJITIMER_OTHER_FINISH_SOUND" => self.finish_sound = Some(sound()?),
+            "MAJITIMER_OTHER_RESTART_SOUND" => self.restart_sound = Some(sound()?),
+            "MAJITIMER_OTHER_REMIND_SOUND" => self.remind_sound = Some(sound()?),
+            "MAJITIMER_OTHER_MILESTONE_SOUND" => self.milestone_sound = Some(sound()?),
+            _ => unreachable!(),
+        }
+        Ok(())
+    }
+    /// 値を上書きしている環境変数の名前
+    pub fn names(&self) -> &[&'static str] {
+        &self.names
+    }
+    fn apply_timer(&self, timer: &Timer) -> Timer {
+        Timer {
+            majitime: self.majitime.unwrap_or(timer.majitime),
+            l: self.l.unwrap_or(timer.l),
+            k: self.k.unwrap_or(timer.k),
+            w0: self.w0.unwrap_or(timer.w0),
+        }
+    }
+    fn apply_other(&self, other: &Other) -> Other {
+        Other {
+            remind: self.remind.unwrap_or(other.remind),
+            finish_sound: self
+                .finish_sound
+                .clone()
+                .unwrap_or_else(|| other.finish_sound.clone()),
+            restart_sound: self
+                .restart_sound
+                .clone()
+                .unwrap_or_else(|| other.restart_sound.clone()),
+            remind_sound: self
+                .remind_sound
+                .clone()
+                .unwrap_or_else(|| other.remind_sound.clone()),
+            milestone_sound: self
+                .milestone_sound
+                .clone()
+                .unwrap_or_else(|| other.milestone_sound.clone()),
+        }
+    }
+}
+
+/// 2つの設定の間で値が異なる項目
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    /// `profiles.default.timer.k`のような項目の位置
+    pub field: String,
+    /// 変更前の値。項目が無かった場合は`None`
+    pub before: Option<String>,
+    /// 変更後の値。項目が無くなった場合は`None`
+    pub after: Option<String>,
 }
+
+impl Change {
+    /// 項目が属するセクション (`profiles.default.timer`や`active`)。インポート時にセクション単位で取り込むために使う
+    pub fn section(&self) -> String {
+        let mut parts = self.field.split('.');
+        match parts.next() {
+            Some("profiles") => {
+                let profile = parts.next().unwrap_or_default();
+                let section = parts.next().unwrap_or_default();
+                let section = section.split('[').next().unwrap_or_default();
+                format!("profiles.{}.{}", profile, section)
+            }
+            Some(field) => field.split('[').next().unwrap_or_default().to_string(),
+            None => String::new(),
+        }
+    }
+}
+
+impl std::fmt::Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} → {}",
+            self.field,
+            self.before.as_deref().unwrap_or("(なし)"),
+            self.after.as_deref().unwrap_or("(なし)")
+        )
+    }
+}
+
+/// JSONの値を`a.b.c`形式の項目ごとに平坦化する
+fn flatten(prefix: String, value: serde_json::Value, output: &mut BTreeMap<String, String>) {
+    match value {
+        serde_json::Value::Object(object) => {
+            for (key, value) in object {
+                let field = if prefix.is_empty() {
+                    key
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                flatten(field, value, output);
+            }
+        }
+        serde_json::Value::Array(array) => {
+            for (index, value) in array.into_iter().enumerate() {
+                flatten(format!("{}[{}]", prefix, index), value, output);
+            }
+        }
+        value => {
+            output.insert(prefix, value.to_string());
+        }
+    }
+}
+
+/// 設定のバンドル (zip) の保存先を選ぶ
+#[cfg(not(target_arch = "wasm32"))]
+pub fn bundle_path_picker() -> Result<path::PathBuf, SettingsErr> {
+    let path = dirs::home_dir().unwrap();
+    rfd::FileDialog::new()
+        .set_directory(&path)
+        .add_filter("zip", &["zip"])
+        .set_file_name("majitimer.zip")
+        .save_file()
+        .ok_or(SettingsErr::SaveCanceled)
+}
+
 #[cfg(not(target_arch = "wasm32"))]
 pub fn path_picker(save_file: bool) -> Result<path::PathBuf, SettingsErr> {
     let path = dirs::home_dir().unwrap();
@@ -207,21 +1167,114 @@ pub enum TimerMode {
     Endurance,
     Rest,
     UrgedToReMajiTime,
+    /// サイクルとは別に使うストップウォッチ
+    Stopwatch,
+    /// サイクルとは別に使うカウントダウン
+    Countdown,
 }
 
+/// 耐久モードとストップウォッチの画面に表示するラップの最大数
+const MAX_LAPS_SHOWN: usize = 10;
+
+/// ストップウォッチとカウントダウンのキーヒント
+fn utility_hints(laps: bool) -> Vec<Span<'static>> {
+    let mut hints: Vec<Span<'static>> = vec![" ポーズ/再開 ".into(), "<Space> ".blue().bold()];
+    if laps {
+        hints.extend([" ラップ ".into(), "<L> ".blue().bold()]);
+    }
+    hints.extend([
+        " サイドタイマー ".into(),
+        "<N> ".blue().bold(),
+        " やり直す ".into(),
+        "<R> ".blue().bold(),
+        " 止める ".into(),
+        "<M> ".blue().bold(),
+        " 終了 ".into(),
+        "<Q> ".blue().bold(),
+    ]);
+    hints
+}
+
+/// 節目に達したときに画面を点滅させる時間
+const MILESTONE_FLASH: std::time::Duration = std::time::Duration::from_secs(3);
+
 pub struct RunData {
-    rodio: (rodio::OutputStream, rodio::OutputStreamHandle),
+    /// 音声出力。最初に音声を再生するときに開く
+    rodio: Option<(rodio::OutputStream, rodio::OutputStreamHandle)>,
     rodio_sink: Option<rodio::Sink>,
     paused: bool,
     mode_transition: bool,
     mode: TimerMode,
+    workflow: WorkflowKind,
     majitime: std::time::Duration,
+    /// 実行中のサイクルの本気モードの時間。手法によっては`majitime`と異なる
+    sprint: std::time::Duration,
     remind: std::time::Duration,
-    l: usize,
-    k: f64,
-    w0: usize,
+    curve: RestCurve,
     up: super::timer::Timer,
     down: super::timer::CountDownTimer,
+    /// 実行中のセッションの記録。`Init`の間は`None`
+    session: Option<history::SessionRecord>,
+    /// 適用中のスケジュールの規則の名前
+    schedule: Option<String>,
+    /// 休憩モードを開始した時刻
+    rest_started: Option<std::time::Instant>,
+    fatigue: Fatigue,
+    /// 実行中のセッションより前に記録された、その日の作業と休憩の累計
+    earlier_today: (chrono::NaiveDate, DayTotals),
+    /// 休憩モードの休憩時間の内訳
+    rest_breakdown: Option<RestTime>,
+    accounting: Accounting,
+    bounds: RestBounds,
+    milestones: Milestones,
+    /// 耐久モードの間に達した節目の数
+    milestone_progress: milestone::Progress,
+    /// 最後に達した節目の説明と、その時刻
+    milestone: Option<(String, std::time::Instant)>,
+    cap: EnduranceCap,
+    long_break: LongBreak,
+    /// 実行中のセッションで何番目のサイクルか (1から数える)
+    cycle: u32,
+    cap_state: cap::CapState,
+    /// 耐久モードの時間の上限についての最後の知らせ
+    cap_notice: Option<String>,
+    /// 実行中のサイクルで終えた本気モードの時間
+    majitime_worked: std::time::Duration,
+    /// 実行中のサイクルで、作業時間として数える間に一時停止していた時間
+    work_paused: std::time::Duration,
+    /// 一時停止した時刻
+    paused_at: Option<std::time::Instant>,
+    /// 耐久モードまたはストップウォッチで記録したラップ
+    laps: Vec<history::LapRecord>,
+    /// 実行中のサイクルの本気モードと耐久モードで手動で増減した記録。休憩に入るときにサイクルの記録に移す
+    adjustments: Vec<history::AdjustRecord>,
+    /// カウントダウンモードで最初にカウントダウンする時間
+    countdown: std::time::Duration,
+    /// カウントダウンモードのカウントダウンが終わったか
+    countdown_finished: bool,
+}
+
+/// 算出した休憩時間の内訳
+#[derive(Debug, Clone, Copy)]
+struct RestTime {
+    /// 耐久モードの時間から曲線で求めた時間
+    curve: std::time::Duration,
+    /// 今日の作業の累計による延長
+    fatigue: std::time::Duration,
+    /// 下限・上限と丸めを適用した休憩時間
+    total: std::time::Duration,
+    /// 一定のサイクルごとに取る長い休憩か
+    long_break: bool,
+}
+
+impl RestTime {
+    fn total(&self) -> std::time::Duration {
+        self.total
+    }
+    /// 下限・上限と丸めによって休憩時間が変わったか
+    fn bounded(&self) -> bool {
+        self.total != self.curve + self.fatigue
+    }
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -232,75 +1285,435 @@ pub enum RunDataErr {
     Paused,
     #[error("音楽プレイヤーの再生に失敗しました")]
     Rodio(#[from] rodio::PlayError),
+    #[error("音声出力を開けませんでした")]
+    Stream(#[from] rodio::StreamError),
+    #[error("{0}")]
+    History(#[from] history::HistoryErr),
 }
 
 impl RunData {
     pub fn new() -> Self {
         Self {
-            rodio: rodio::OutputStream::try_default().unwrap(),
+            rodio: None,
             rodio_sink: None,
             paused: true,
             mode_transition: false,
             mode: TimerMode::Init,
+            workflow: WorkflowKind::default(),
             majitime: std::time::Duration::ZERO,
+            sprint: std::time::Duration::ZERO,
             remind: std::time::Duration::ZERO,
-            l: 0,
-            k: 0.0,
-            w0: 0,
+            curve: RestCurve::default(),
             up: super::timer::Timer::new(),
             down: super::timer::CountDownTimer::new(),
+            session: None,
+            schedule: None,
+            rest_started: None,
+            fatigue: Fatigue::default(),
+            earlier_today: (chrono::Local::now().date_naive(), DayTotals::default()),
+            rest_breakdown: None,
+            accounting: Accounting::default(),
+            bounds: RestBounds::default(),
+            milestones: Milestones::default(),
+            milestone_progress: milestone::Progress::default(),
+            milestone: None,
+            cap: EnduranceCap::default(),
+            long_break: LongBreak::default(),
+            cycle: 0,
+            cap_state: cap::CapState::default(),
+            cap_notice: None,
+            majitime_worked: std::time::Duration::ZERO,
+            work_paused: std::time::Duration::ZERO,
+            paused_at: None,
+            laps: Vec::new(),
+            adjustments: Vec::new(),
+            countdown: std::time::Duration::ZERO,
+            countdown_finished: false,
         }
     }
-    pub fn init(&mut self, settings: &Settings) {
-        self.majitime = std::time::Duration::from_secs(
-            (settings.timer.majitime_min * 60 + settings.timer.majitime_sec) as u64,
-        );
-        self.remind = std::time::Duration::from_secs(settings.other.remind as u64);
-        self.l = settings.timer.l_min * 60 + settings.timer.l_sec;
-        self.k = settings.timer.k;
-        self.w0 = settings.timer.w0_min * 60 + settings.timer.w0_sec;
+    pub fn init(&mut self, settings: &Settings) -> Result<(), RunDataErr> {
+        // 履歴の読み書きに失敗した場合に動作していない状態のまま残らないよう、状態を変える前に行う
+        self.finish_session()?;
+        self.load_earlier_today()?;
+        self.load_params(settings);
 
-        self.mode = TimerMode::MajiTime;
         self.paused = false;
-        self.down.init(self.majitime);
+        self.paused_at = None;
+        self.cycle = 0;
+        self.session = Some(history::SessionRecord::new(
+            settings.active_profile(),
+            self.workflow,
+        ));
+        self.rest_started = None;
+        self.rest_breakdown = None;
+
+        self.start_cycle();
+        let step = self.workflow.workflow().start(self.majitime);
+        self.enter(step);
+        Ok(())
     }
-    pub fn update(&mut self, settings: &Settings) {
-        self.majitime = std::time::Duration::from_secs(
-            (settings.timer.majitime_min * 60 + settings.timer.majitime_sec) as u64,
-        );
-        self.remind = std::time::Duration::from_secs(settings.other.remind as u64);
-        self.l = settings.timer.l_min * 60 + settings.timer.l_sec;
-        self.k = settings.timer.k;
-        self.w0 = settings.timer.w0_min * 60 + settings.timer.w0_sec;
+    /// 設定の変更を反映する
+    /// 動作中のカウントダウンを変えないよう、タイマーのパラメーターは次のサイクルの開始時に反映される
+    pub fn update(&mut self, settings: &Settings) -> Result<(), RunDataErr> {
+        if self.mode == TimerMode::Init {
+            self.load_params(settings);
+        }
+
+        // プロファイルが切り替わった場合は、それまでの記録を区切って新しいセッションとして記録する
+        // 履歴の書き込みに失敗しても、以降のサイクルを記録できるよう新しいセッションを作ってからエラーを返す
+        if let Some(session) = &self.session {
+            if session.profile != settings.active_profile() {
+                let result = self
+                    .finish_session()
+                    .and_then(|_| self.load_earlier_today());
+                self.session = Some(history::SessionRecord::new(
+                    settings.active_profile(),
+                    self.workflow,
+                ));
+                return result;
+            }
+        }
+        Ok(())
+    }
+    /// 現在の曜日と時刻に当てはまる`Timer`のパラメーターを読み込む
+    fn load_params(&mut self, settings: &Settings) {
+        let (timer, rule) = settings.scheduled_timer(&chrono::Local::now().naive_local());
+        self.majitime = timer.majitime;
+        self.workflow = settings.workflow();
+        self.curve = timer.curve();
+        self.schedule = rule.map(str::to_owned);
+        self.remind = settings.other().remind;
+        self.fatigue = settings.fatigue().clone();
+        self.accounting = settings.accounting().clone();
+        self.bounds = settings.bounds().clone();
+        self.milestones = settings.milestones().clone();
+        self.cap = settings.cap().clone();
+        self.long_break = settings.long_break().clone();
+    }
+    /// 本気モードの開始時に、サイクルの作業時間の集計をやり直す
+    fn start_cycle(&mut self) {
+        self.cycle += 1;
+        self.majitime_worked = std::time::Duration::ZERO;
+        self.work_paused = std::time::Duration::ZERO;
+        self.adjustments.clear();
+    }
+    /// 本気モードの時間を作業時間として数えるか
+    /// 本気モードの後にすぐ休憩する手法では、本気モードの時間が作業時間そのものになる
+    fn sprint_counts_as_work(&self) -> bool {
+        self.accounting.include_majitime || self.workflow.workflow().after_sprint() == Step::Rest
+    }
+    /// `mode`の間の時間を作業時間として数えるか
+    fn counts_as_work(&self) -> bool {
+        match self.mode {
+            TimerMode::Endurance => true,
+            TimerMode::MajiTime => self.sprint_counts_as_work(),
+            _ => false,
+        }
+    }
+    /// 休憩時間の算出に使う、実行中のサイクルの作業時間
+    fn work_time(&self) -> std::time::Duration {
+        let mut work = self.up.get_time();
+        if self.sprint_counts_as_work() {
+            work += match self.mode {
+                TimerMode::MajiTime => self.sprint.saturating_sub(self.down.get_time()),
+                _ => self.majitime_worked,
+            };
+        }
+        if !self.accounting.exclude_pauses {
+            work += self.work_paused;
+            if let Some(paused_at) = self.paused_at.filter(|_| self.counts_as_work()) {
+                work += paused_at.elapsed();
+            }
+        }
+        work
+    }
+    /// ストップウォッチを最初から始める
+    pub fn start_stopwatch(&mut self) -> Result<(), RunDataErr> {
+        self.end_session()?;
+        self.mode = TimerMode::Stopwatch;
+        self.paused = false;
+        self.up.init();
+        Ok(())
+    }
+    /// `duration`のカウントダウンを始める
+    pub fn start_countdown(&mut self, duration: std::time::Duration) -> Result<(), RunDataErr> {
+        self.end_session()?;
+        self.mode = TimerMode::Countdown;
+        self.paused = false;
+        self.countdown = duration;
+        self.down.init(duration);
+        Ok(())
+    }
+    /// 実行中のストップウォッチまたはカウントダウンを最初からやり直す
+    pub fn restart_utility(&mut self) -> Result<(), RunDataErr> {
+        match self.mode {
+            TimerMode::Stopwatch => self.start_stopwatch(),
+            TimerMode::Countdown => self.start_countdown(self.countdown),
+            _ => Ok(()),
+        }
+    }
+    /// 耐久モードまたはストップウォッチの現在の経過時間をラップとして記録する
+    /// * 戻り値 => 記録できた場合は`true`
+    pub fn lap(&mut self) -> bool {
+        if self.paused || !matches!(self.mode, TimerMode::Endurance | TimerMode::Stopwatch) {
+            return false;
+        }
+        self.laps.push(history::LapRecord {
+            at: self.up.get_time(),
+            label: None,
+        });
+        true
+    }
+    /// 実行中のタイマーの時間を`amount`だけ増減し、履歴に記録する
+    /// 本気モード、休憩モード、リマインドでは残り時間を、耐久モードでは経過時間を増減する
+    /// * 戻り値 => 増減したタイマー
+    pub fn adjust(
+        &mut self,
+        amount: std::time::Duration,
+        decrease: bool,
+    ) -> Option<history::AdjustTarget> {
+        let target = match self.mode {
+            TimerMode::MajiTime => {
+                // 本気モードの時間そのものを増減し、作業した時間の計算を合わせる
+                if decrease {
+                    self.down.shorten(amount);
+                    self.sprint = self.sprint.saturating_sub(amount);
+                } else {
+                    self.down.extend(amount);
+                    self.sprint += amount;
+                }
+                history::AdjustTarget::Sprint
+            }
+            TimerMode::Endurance => {
+                if decrease {
+                    self.up.sub(amount);
+                } else {
+                    self.up.add(amount);
+                }
+                history::AdjustTarget::Endurance
+            }
+            TimerMode::Rest | TimerMode::UrgedToReMajiTime => {
+                if decrease {
+                    self.down.shorten(amount);
+                } else {
+                    self.down.extend(amount);
+                }
+                if self.mode == TimerMode::Rest {
+                    history::AdjustTarget::Rest
+                } else {
+                    history::AdjustTarget::Remind
+                }
+            }
+            _ => return None,
+        };
+        let record = history::AdjustRecord {
+            target,
+            amount,
+            decreased: decrease,
+        };
+        match target {
+            history::AdjustTarget::Sprint | history::AdjustTarget::Endurance => {
+                self.adjustments.push(record)
+            }
+            // 休憩に入った時点でサイクルの記録は作られているため、直接追加する
+            history::AdjustTarget::Rest | history::AdjustTarget::Remind => {
+                if let Some(cycle) = self
+                    .session
+                    .as_mut()
+                    .and_then(|session| session.cycles.last_mut())
+                {
+                    cycle.adjustments.push(record);
+                }
+            }
+        }
+        Some(target)
+    }
+    /// 最後に記録したラップに名前を付ける
+    pub fn label_last_lap(&mut self, label: &str) {
+        let label = label.trim();
+        if let Some(lap) = self.laps.last_mut().filter(|_| !label.is_empty()) {
+            lap.label = Some(label.to_string());
+        }
+    }
+    /// ラップの一覧。新しいラップから順に、区間の時間と合計の時間を表示する
+    fn lap_lines(&self) -> Vec<Line<'static>> {
+        let mut previous = std::time::Duration::ZERO;
+        let mut lines: Vec<Line<'static>> = Vec::new();
+        for (index, lap) in self.laps.iter().enumerate() {
+            lines.push(Line::from(format!(
+                "ラップ{:>3}  {}  (合計 {})  {}",
+                index + 1,
+                lap.at.saturating_sub(previous).to_time_string(),
+                lap.at.to_time_string(),
+                lap.label.as_deref().unwrap_or("")
+            )));
+            previous = lap.at;
+        }
+        lines.into_iter().rev().take(MAX_LAPS_SHOWN).collect()
+    }
+    /// セッションを終了し、`Init`に戻る
+    pub fn end_session(&mut self) -> Result<(), RunDataErr> {
+        let result = self.finish_session();
+        self.mode = TimerMode::Init;
+        self.laps.clear();
+        self.adjustments.clear();
+        self.countdown_finished = false;
+        self.paused = true;
+        self.paused_at = None;
+        self.mode_transition = false;
+        self.up = super::timer::Timer::new();
+        self.down = super::timer::CountDownTimer::new();
+        self.rest_started = None;
+        self.rest_breakdown = None;
+        result
+    }
+    /// 履歴ファイルから、今日記録されたセッションの作業と休憩の累計を読み込む
+    fn load_earlier_today(&mut self) -> Result<(), RunDataErr> {
+        let today = chrono::Local::now().date_naive();
+        self.earlier_today = (today, DayTotals::from_sessions(&history::load()?, today));
+        Ok(())
+    }
+    /// 実行中のセッションの終えたサイクルを含めた、今日の作業と休憩の累計
+    fn today(&self) -> DayTotals {
+        let (date, earlier) = self.earlier_today;
+        let mut totals = if date == chrono::Local::now().date_naive() {
+            earlier
+        } else {
+            DayTotals::default()
+        };
+        if let Some(session) = &self.session {
+            totals.add_cycles(&session.cycles);
+        }
+        totals
+    }
+    /// 適用中のスケジュールの規則の名前
+    pub fn schedule(&self) -> Option<&str> {
+        self.schedule.as_deref()
+    }
+    /// 実行中のセッションの記録
+    pub fn session(&self) -> Option<&history::SessionRecord> {
+        self.session.as_ref()
+    }
+    /// 実行中のセッションを終了し、履歴ファイルに記録する
+    pub fn finish_session(&mut self) -> Result<(), RunDataErr> {
+        if let Some(mut session) = self.session.take() {
+            session.ended_at = Some(chrono::Local::now());
+            // 休憩に入る前に終えたサイクルのラップも残す
+            session.laps = std::mem::take(&mut self.laps);
+            session.adjustments = std::mem::take(&mut self.adjustments);
+            history::append(&session)?;
+        }
+        Ok(())
     }
     pub fn pause_or_resume(&mut self) {
         if self.paused {
-            match self.mode {
-                TimerMode::MajiTime | TimerMode::Rest | TimerMode::UrgedToReMajiTime => {
-                    self.down.resume()
+            if let Some(paused_at) = self.paused_at.take() {
+                if self.counts_as_work() {
+                    self.work_paused += paused_at.elapsed();
                 }
-                TimerMode::Endurance => self.up.resume(),
+            }
+            match self.mode {
+                TimerMode::MajiTime
+                | TimerMode::Rest
+                | TimerMode::UrgedToReMajiTime
+                | TimerMode::Countdown => self.down.resume(),
+                TimerMode::Endurance | TimerMode::Stopwatch => self.up.resume(),
                 _ => unreachable!(),
             }
         } else {
             match self.mode {
-                TimerMode::MajiTime | TimerMode::Rest | TimerMode::UrgedToReMajiTime => {
-                    self.down.pause()
-                }
-                TimerMode::Endurance => self.up.pause(),
+                TimerMode::MajiTime
+                | TimerMode::Rest
+                | TimerMode::UrgedToReMajiTime
+                | TimerMode::Countdown => self.down.pause(),
+                TimerMode::Endurance | TimerMode::Stopwatch => self.up.pause(),
                 _ => unreachable!(),
             }
+            self.paused_at = Some(std::time::Instant::now());
         }
         self.paused = !self.paused;
     }
-    // r = L / (1 + e^(-k * (w - w_0)))
-    fn calc_rest_time(&self) -> std::time::Duration {
-        let w = self.up.get_time().as_secs_f64();
-        // let w = std::time::Duration::from_secs(30).as_secs_f64();
-        let r = self.l as f64 / (1.0 + std::f64::consts::E.powf(-self.k * (w - self.w0 as f64)));
+    // r = L / (1 + e^(-k * (w - w_0))) + fatigue
+    // 下限・上限と丸めは疲労の項を加えた後に適用する
+    fn calc_rest_time(&self) -> RestTime {
+        self.rest_time_at(self.work_time(), self.today())
+    }
+    /// 実行中のサイクルで`work`だけ作業したときの休憩時間
+    /// * today => 実行中のサイクルより前の、今日の作業と休憩の累計
+    fn rest_time_at(&self, work: std::time::Duration, mut today: DayTotals) -> RestTime {
+        // 手法が休憩時間を決める場合は、曲線に関する設定を適用しない
+        if let Some(rest) = self.workflow.workflow().rest(work, self.cycle) {
+            return RestTime {
+                curve: rest.time,
+                fatigue: std::time::Duration::ZERO,
+                total: rest.time,
+                long_break: rest.long_break,
+            };
+        }
+        let (curve, long_break) = match self.long_break.rest(self.cycle, self.curve) {
+            // 決まった時間の長い休憩には、疲労の項や下限・上限を適用しない
+            Some(LongRest::Fixed(fixed)) => {
+                return RestTime {
+                    curve: fixed,
+                    fatigue: std::time::Duration::ZERO,
+                    total: fixed,
+                    long_break: true,
+                }
+            }
+            Some(LongRest::Curve(curve)) => (curve, true),
+            None => (self.curve, false),
+        };
+        today.work += work;
+        let curve = curve.rest(work);
+        let fatigue = self.fatigue.extra(&today);
+        RestTime {
+            curve,
+            fatigue,
+            total: self.bounds.apply(work, curve + fatigue),
+            long_break,
+        }
+    }
+    /// 耐久モードで今休憩に入った場合の休憩時間の見込み
+    /// * .0 => 今休憩に入った場合の休憩時間
+    /// * .1 => あと1分作業した場合に増える休憩時間
+    /// * .2 => 休憩時間が次の1分に達するまでの作業時間。これ以上増えない場合は`None`
+    fn rest_forecast(
+        &self,
+    ) -> (
+        std::time::Duration,
+        std::time::Duration,
+        Option<std::time::Duration>,
+    ) {
+        const MINUTE: std::time::Duration = std::time::Duration::from_secs(60);
+        // 1日より先は探さない
+        const HORIZON: std::time::Duration = std::time::Duration::from_secs(24 * 60 * 60);
+
+        let today = self.today();
+        let work = self.work_time();
+        let rest_at = |extra: std::time::Duration| self.rest_time_at(work + extra, today).total();
+        let now = rest_at(std::time::Duration::ZERO);
+        let marginal = rest_at(MINUTE).saturating_sub(now);
 
-        // panic!("r = {}\nl = {}\nk = {}\nw = {}\nw0 = {}", r, self.l, self.k, w, self.w0);
-        std::time::Duration::from_secs_f64(r)
+        // 休憩時間は作業時間に対して単調に増えるため、次の1分を超える時間を倍々に広げてから二分探索する
+        let target = (now.as_secs() / 60 + 1) * 60;
+        let reached = |extra: std::time::Duration| rest_at(extra).as_secs() >= target;
+        let mut high = std::time::Duration::from_secs(1);
+        while !reached(high) {
+            if high > HORIZON {
+                return (now, marginal, None);
+            }
+            high *= 2;
+        }
+        let mut low = std::time::Duration::ZERO;
+        while high - low > std::time::Duration::from_secs(1) {
+            let middle = (low + high) / 2;
+            if reached(middle) {
+                high = middle;
+            } else {
+                low = middle;
+            }
+        }
+        (now, marginal, Some(high))
     }
     fn play_sound(&mut self, path: Result<&str, GetPathErr>) -> Result<(), RunDataErr> {
         // assert_eq!(path.is_ok(), false);
@@ -321,8 +1734,12 @@ impl RunData {
             //     .play_once(std::io::BufReader::new(file))?;
             // audacity.set_volume(0.05);
 
-            let file = std::fs::File::open(path)?;
-            self.rodio_sink = Some(self.rodio.1.play_once(std::io::BufReader::new(file))?);
+            let file = std::fs::File::open(resolve_path(path))?;
+            if self.rodio.is_none() {
+                self.rodio = Some(rodio::OutputStream::try_default()?);
+            }
+            let handle = &self.rodio.as_ref().expect("opened above").1;
+            self.rodio_sink = Some(handle.play_once(std::io::BufReader::new(file))?);
             self.rodio_sink.as_ref().unwrap().set_volume(0.1);
         }
         Ok(())
@@ -336,8 +1753,61 @@ impl RunData {
     pub fn mode(&self) -> &TimerMode {
         &self.mode
     }
+    /// 手法が決めたサイクルの段階に入る
+    fn enter(&mut self, step: Step) {
+        match step {
+            Step::Sprint(sprint) => {
+                self.mode = TimerMode::MajiTime;
+                self.sprint = sprint;
+                self.up = super::timer::Timer::new();
+                self.down.init(sprint);
+            }
+            Step::Endurance => {
+                self.mode = TimerMode::Endurance;
+                self.milestone_progress = milestone::Progress::default();
+                self.milestone = None;
+                self.cap_state = cap::CapState::default();
+                self.cap_notice = None;
+                self.laps.clear();
+                self.down = super::timer::CountDownTimer::new();
+                self.up.init();
+            }
+            Step::Rest => {
+                let rest_time = self.calc_rest_time();
+                let rest = rest_time.total();
+                self.rest_breakdown = Some(rest_time);
+                let work = self.work_time();
+                if let Some(session) = &mut self.session {
+                    session.cycles.push(history::CycleRecord {
+                        endurance: self.up.get_time(),
+                        work: Some(work),
+                        rest,
+                        taken: None,
+                        long_break: rest_time.long_break,
+                        laps: std::mem::take(&mut self.laps),
+                        adjustments: std::mem::take(&mut self.adjustments),
+                    });
+                }
+                self.mode = TimerMode::Rest;
+                self.rest_started = Some(std::time::Instant::now());
+                self.down.init(rest);
+                self.up = super::timer::Timer::new();
+            }
+        }
+    }
     pub fn state_process(&mut self, settings: &Settings) -> Result<(), RunDataErr> {
         if self.paused {
+            // 一時停止が長すぎる場合は、作業を中断したものとしてセッションを終了する
+            // セッションを持たないストップウォッチとカウントダウンは対象外
+            if let (Some(max_pause), Some(paused_at), true) = (
+                self.accounting.max_pause,
+                self.paused_at,
+                self.session.is_some(),
+            ) {
+                if paused_at.elapsed() > max_pause {
+                    self.end_session()?;
+                }
+            }
             return Ok(());
         }
 
@@ -349,22 +1819,53 @@ impl RunData {
                 if self.down.get_time() == std::time::Duration::ZERO {
                     self.mode_transition = false;
 
-                    self.mode = TimerMode::Endurance;
-                    self.down = super::timer::CountDownTimer::new();
+                    self.majitime_worked = self.sprint;
+                    let step = self.workflow.workflow().after_sprint();
+                    self.enter(step);
 
-                    self.up.init();
-
-                    self.play_sound(settings.other.get_finish_sound())?;
+                    self.play_sound(settings.other().get_finish_sound())?;
                 }
             }
             TimerMode::Endurance => {
+                let rest = self.calc_rest_time().total();
+                let reached = self.milestones.check(
+                    &mut self.milestone_progress,
+                    self.up.get_time(),
+                    rest,
+                    self.curve.l,
+                );
+                if let Some(reached) = reached {
+                    self.milestone = Some((reached, std::time::Instant::now()));
+                    self.play_sound(settings.other().get_milestone_sound())?;
+                }
+                match self.cap.check(&mut self.cap_state, self.up.get_time()) {
+                    Some(CapEvent::Warn(remaining)) => {
+                        self.cap_notice = Some(format!(
+                            "あと{}で上限に達し、自動で休憩に入ります",
+                            remaining.to_time_string()
+                        ));
+                        self.play_sound(settings.other().get_remind_sound())?;
+                    }
+                    Some(CapEvent::Force) => {
+                        self.cap_notice =
+                            Some("耐久モードの上限に達したため、休憩に入りました".to_string());
+                        self.mode_transition_start();
+                    }
+                    Some(CapEvent::Remind(over)) => {
+                        self.cap_notice =
+                            Some(format!("目安の時間を{}超えています", over.to_time_string()));
+                        self.play_sound(settings.other().get_remind_sound())?;
+                    }
+                    None => {}
+                }
                 if self.mode_transition {
                     self.mode_transition = false;
 
-                    self.mode = TimerMode::Rest;
-
-                    self.down.init(self.calc_rest_time());
-                    self.up = super::timer::Timer::new();
+                    // 上限に達して休憩に入った場合だけ、休憩モードでもその旨を表示する
+                    if !self.cap.reached(self.up.get_time()) {
+                        self.cap_notice = None;
+                    }
+                    self.enter(Step::Rest);
                     // self.down.init(std::time::Duration::from_secs(30));
                 }
             }
@@ -376,37 +1877,106 @@ impl RunData {
 
                     self.down.init(self.remind);
 
-                    self.play_sound(settings.other.get_restart_sound())?;
+                    self.play_sound(settings.other().get_restart_sound())?;
                 }
             }
             TimerMode::UrgedToReMajiTime => {
                 if self.mode_transition {
                     self.mode_transition = false;
 
-                    self.mode = TimerMode::MajiTime;
-                    self.up = super::timer::Timer::new();
+                    // 休憩モードの開始から本気モードを再開するまでの時間を、実際の休憩時間として記録する
+                    if let (Some(rest_started), Some(cycle)) = (
+                        self.rest_started.take(),
+                        self.session
+                            .as_mut()
+                            .and_then(|session| session.cycles.last_mut()),
+                    ) {
+                        cycle.taken = Some(rest_started.elapsed());
+                    }
 
-                    self.down.init(self.majitime);
+                    // サイクルの切れ目で、設定の変更とスケジュールを反映する
+                    self.load_params(settings);
+                    // 手法が切り替わった場合は、それまでの記録を区切って新しいセッションとして記録する
+                    // 履歴の書き込みに失敗しても、サイクルは進めてからエラーを返す
+                    let mut result = Ok(());
+                    if self
+                        .session
+                        .as_ref()
+                        .is_some_and(|session| session.workflow != self.workflow)
+                    {
+                        // 区切ったセッションのサイクルも今日の累計に含めるため、履歴から読み込み直す
+                        result = self
+                            .finish_session()
+                            .and_then(|_| self.load_earlier_today());
+                        self.session = Some(history::SessionRecord::new(
+                            settings.active_profile(),
+                            self.workflow,
+                        ));
+                    }
+                    self.start_cycle();
+                    let step = self.workflow.workflow().start(self.majitime);
+                    self.enter(step);
 
-                    return Ok(());
+                    return result;
                 }
                 // リマインドの時間が来たら、タイマーリセット後remind_soundを再生
                 if self.down.get_time() == std::time::Duration::ZERO {
                     self.down.init(self.remind);
-                    self.play_sound(settings.other.get_remind_sound())?;
+                    self.play_sound(settings.other().get_remind_sound())?;
+                }
+            }
+            TimerMode::Stopwatch => {}
+            TimerMode::Countdown => {
+                if self.down.get_time() == std::time::Duration::ZERO {
+                    // 終わったらfinish_soundを再生し、止めるまでremind_soundでリマインドする
+                    self.down.init(settings.other().remind);
+                    if self.countdown_finished {
+                        self.play_sound(settings.other().get_remind_sound())?;
+                    } else {
+                        self.countdown_finished = true;
+                        self.play_sound(settings.other().get_finish_sound())?;
+                    }
                 }
             }
         }
 
         Ok(())
     }
+    /// 実行中のサイクルの番号と、そのサイクルの休憩が長い休憩かどうかの表示
+    fn cycle_label(&self) -> Span<'static> {
+        let long_break = match self.mode {
+            TimerMode::Rest | TimerMode::UrgedToReMajiTime => self
+                .rest_breakdown
+                .is_some_and(|rest_time| rest_time.long_break),
+            _ => self.calc_rest_time().long_break,
+        };
+        let mut label = match (long_break, &self.mode) {
+            (true, TimerMode::MajiTime | TimerMode::Endurance) => {
+                format!(" サイクル {} (次は長い休憩)", self.cycle)
+            }
+            (true, _) => format!(" サイクル {} (長い休憩)", self.cycle),
+            (false, _) => format!(" サイクル {}", self.cycle),
+        };
+        if self.workflow != WorkflowKind::Maji {
+            label = format!("{} · {}", label, self.workflow.workflow().name());
+        }
+        label.dark_gray()
+    }
+    /// 節目に達した直後の点滅で、強調して表示する間であれば`true`
+    fn milestone_flashing(&self) -> bool {
+        self.milestone.as_ref().is_some_and(|(_, reached_at)| {
+            let elapsed = reached_at.elapsed();
+            elapsed < MILESTONE_FLASH && elapsed.as_millis() / 500 % 2 == 0
+        })
+    }
     pub fn render_time(&self) -> String {
         match self.mode {
             TimerMode::Init => "UNREACHABLE".to_string(),
-            TimerMode::MajiTime | TimerMode::Rest | TimerMode::UrgedToReMajiTime => {
-                self.down.get_time().to_time_string()
-            }
-            TimerMode::Endurance => self.up.get_time().to_time_string(),
+            TimerMode::MajiTime
+            | TimerMode::Rest
+            | TimerMode::UrgedToReMajiTime
+            | TimerMode::Countdown => self.down.get_time().to_time_string(),
+            TimerMode::Endurance | TimerMode::Stopwatch => self.up.get_time().to_time_string(),
         }
     }
     /// ratatuiでレンダリングする文字列のリストを返す
@@ -426,6 +1996,20 @@ impl RunData {
             "<I> ".blue().bold(),
             " 設定のエクスポート ".into(),
             "<E> ".blue().bold(),
+            " バンドルのエクスポート ".into(),
+            "<B> ".blue().bold(),
+            " プロファイル ".into(),
+            "<P> ".blue().bold(),
+            " プリセット ".into(),
+            "<T> ".blue().bold(),
+            " 記録から調整 ".into(),
+            "<F> ".blue().bold(),
+            " 前の設定に戻す ".into(),
+            "<U> ".blue().bold(),
+            " サイドタイマー ".into(),
+            "<N> ".blue().bold(),
+            " 時間の調整 ".into(),
+            "<+/-> ".blue().bold(),
             " 終了 ".into(),
             "<Q> ".blue().bold(),
         ];
@@ -439,17 +2023,33 @@ impl RunData {
                 vec![
                     " スタート ".into(),
                     "<Space> ".blue().bold(),
+                    " ストップウォッチ ".into(),
+                    "<S> ".blue().bold(),
+                    " カウントダウン ".into(),
+                    "<C> ".blue().bold(),
                     " 設定のインポート ".into(),
                     "<I> ".blue().bold(),
                     " 設定のエクスポート ".into(),
                     "<E> ".blue().bold(),
+                    " バンドルのエクスポート ".into(),
+                    "<B> ".blue().bold(),
+                    " プロファイル ".into(),
+                    "<P> ".blue().bold(),
+                    " プリセット ".into(),
+                    "<T> ".blue().bold(),
+                    " 記録から調整 ".into(),
+                    "<F> ".blue().bold(),
+                    " 前の設定に戻す ".into(),
+                    "<U> ".blue().bold(),
+                    " サイドタイマー ".into(),
+                    "<N> ".blue().bold(),
                     " 終了 ".into(),
                     "<Q> ".blue().bold(),
                 ],
             ),
             TimerMode::MajiTime => (
                 vec![
-                    Line::from("本気モード\n".red().bold()),
+                    Line::from(vec!["本気モード\n".red().bold(), self.cycle_label()]),
                     Line::from(self.down.get_time().to_time_string()),
                     Line::from("短時間だけ本気で作業をする時間です。休憩厳禁！だけど超短い！"),
                 ],
@@ -457,7 +2057,14 @@ impl RunData {
             ),
             TimerMode::Endurance => (
                 vec![
-                    Line::from(" 耐久モード\n".green().bold()),
+                    if self.milestone_flashing() {
+                        Line::from(vec![
+                            " 耐久モード\n".black().on_green().bold(),
+                            self.cycle_label(),
+                        ])
+                    } else {
+                        Line::from(vec![" 耐久モード\n".green().bold(), self.cycle_label()])
+                    },
                     Line::from(self.up.get_time().to_time_string()),
                     Line::from(vec![
                         "もうやりたくないと思うまで作業をする時間です。".into(),
@@ -465,22 +2072,73 @@ impl RunData {
                         "で休憩に入ることができますが、長く作業すればその分長く休憩できます。\n"
                             .into(),
                     ]),
-                ],
-                except_init,
+                    {
+                        let (rest, marginal, next) = self.rest_forecast();
+                        Line::from(format!(
+                            "今休憩すると {}  (あと1分で +{}、{})",
+                            rest.to_time_string(),
+                            marginal.to_time_string(),
+                            match next {
+                                Some(next) => format!("次の1分まで {}", next.to_time_string()),
+                                None => "これ以上は増えません".to_string(),
+                            }
+                        ))
+                    },
+                    match &self.cap_notice {
+                        Some(notice) => Line::from(notice.clone().red().bold()),
+                        None => Line::from(""),
+                    },
+                    match &self.milestone {
+                        Some((reached, _)) if self.milestone_flashing() => {
+                            Line::from(format!("節目: {}", reached).yellow().bold())
+                        }
+                        Some((reached, _)) => Line::from(format!("節目: {}", reached).dark_gray()),
+                        None => Line::from(""),
+                    },
+                ]
+                .into_iter()
+                .chain(self.lap_lines())
+                .collect(),
+                [vec![" ラップ ".into(), "<L> ".blue().bold()], except_init].concat(),
             ),
             TimerMode::Rest => (
                 vec![
-                    Line::from(" 休憩モード \n".cyan().bold()),
+                    Line::from(vec![" 休憩モード \n".cyan().bold(), self.cycle_label()]),
                     Line::from(self.down.get_time().to_time_string()),
                     Line::from(
                         "耐久モードで作業した時間に応じた休憩を取る時間です。十分に休みましょう。",
                     ),
+                    match self.rest_breakdown {
+                        Some(rest_time) if !rest_time.fatigue.is_zero() || rest_time.bounded() => {
+                            Line::from(format!(
+                                "内訳: 耐久モード {} + 今日の疲労 {}{}",
+                                rest_time.curve.to_time_string(),
+                                rest_time.fatigue.to_time_string(),
+                                if rest_time.bounded() {
+                                    format!(
+                                        " → 下限・上限と丸めを適用して {}",
+                                        rest_time.total().to_time_string()
+                                    )
+                                } else {
+                                    String::new()
+                                }
+                            ))
+                        }
+                        _ => Line::from(""),
+                    },
+                    match &self.cap_notice {
+                        Some(notice) => Line::from(notice.clone().red().bold()),
+                        None => Line::from(""),
+                    },
                 ],
                 except_init,
             ),
             TimerMode::UrgedToReMajiTime => (
                 vec![
-                    Line::from(" 本気モードを手動で開始してください \n".yellow().bold()),
+                    Line::from(vec![
+                        " 本気モードを手動で開始してください \n".yellow().bold(),
+                        self.cycle_label(),
+                    ]),
                     Line::from(self.down.get_time().to_time_string()),
                     Line::from(vec![
                         "休憩モードでの休憩時間が終わりました。".into(),
@@ -490,11 +2148,311 @@ impl RunData {
                 ],
                 except_init,
             ),
+            TimerMode::Stopwatch => {
+                let mut lines = vec![
+                    Line::from(" ストップウォッチ ".magenta().bold()),
+                    Line::from(self.up.get_time().to_time_string()),
+                    Line::from(vec![
+                        "サイクルとは別に時間を計ります。".into(),
+                        " <L> ".blue().bold(),
+                        "でラップを記録できます。".into(),
+                    ]),
+                ];
+                lines.extend(self.lap_lines());
+                (lines, utility_hints(true))
+            }
+            TimerMode::Countdown => (
+                vec![
+                    Line::from(" カウントダウン ".magenta().bold()),
+                    Line::from(self.down.get_time().to_time_string()),
+                    if self.countdown_finished {
+                        Line::from(vec![
+                            "時間になりました。".yellow().bold(),
+                            " <M> ".blue().bold(),
+                            "で止めることができます。".into(),
+                        ])
+                    } else {
+                        Line::from(format!(
+                            "サイクルとは別に{}をカウントダウンします。",
+                            self.countdown.to_time_string()
+                        ))
+                    },
+                ],
+                utility_hints(false),
+            ),
         }
     }
 }
 
 #[cfg(test)]
-fn test_calc_rest_time() {
-    
+mod tests {
+    use super::*;
+    use crate::timer::minutes;
+
+    /// `default`に加えて、曲線を変えた`admin`プロファイルを持つ設定
+    fn with_admin() -> Settings {
+        let mut settings = Settings::template();
+        let admin = Profile {
+            timer: Timer::new(minutes(1), minutes(10), 0.01, minutes(20)),
+            ..Profile::default()
+        };
+        settings.profiles.insert("admin".to_string(), admin);
+        settings
+    }
+
+    fn rule(json: &str) -> ScheduleRule {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn at(date: &str, time: &str) -> chrono::NaiveDateTime {
+        chrono::NaiveDateTime::parse_from_str(&format!("{} {}", date, time), "%Y-%m-%d %H:%M")
+            .unwrap()
+    }
+
+    #[test]
+    fn schedule_overrides_only_given_fields() {
+        let mut settings = with_admin();
+        settings.active = "admin".to_string();
+        settings.profiles.get_mut("admin").unwrap().schedule = vec![rule(
+            r#"{ "name": "週末", "days": ["sat", "sun"], "timer": { "majitime": "5m" } }"#,
+        )];
+
+        // 2026-10-17は土曜日
+        let (timer, name) = settings.scheduled_timer(&at("2026-10-17", "10:00"));
+        assert_eq!(name, Some("週末"));
+        assert_eq!(timer.majitime, minutes(5));
+        assert_eq!(timer.l, minutes(10));
+        assert_eq!(timer.k, 0.01);
+        assert_eq!(timer.w0, minutes(20));
+
+        let (timer, name) = settings.scheduled_timer(&at("2026-10-19", "10:00"));
+        assert_eq!(name, None);
+        assert_eq!(timer.majitime, minutes(1));
+    }
+
+    #[test]
+    fn schedule_window_crosses_midnight() {
+        let night = rule(r#"{ "name": "夜", "from": "18:00", "to": "06:00" }"#);
+        assert!(night.matches(&at("2026-10-19", "18:00")));
+        assert!(night.matches(&at("2026-10-19", "23:59")));
+        assert!(night.matches(&at("2026-10-20", "05:59")));
+        assert!(!night.matches(&at("2026-10-20", "06:00")));
+        assert!(!night.matches(&at("2026-10-19", "12:00")));
+
+        let day = rule(r#"{ "name": "昼", "days": ["mon"], "from": "09:00", "to": "17:00" }"#);
+        assert!(day.matches(&at("2026-10-19", "09:00")));
+        assert!(!day.matches(&at("2026-10-19", "17:00")));
+        assert!(!day.matches(&at("2026-10-20", "12:00")));
+    }
+
+    #[test]
+    fn init_stays_stopped_when_history_fails() {
+        // 読み込めない履歴ファイル
+        with_config_dir("init", &[0xff, 0xfe, b'\n'], || {
+            let settings = Settings::template();
+            let mut run = RunData::new();
+            assert!(run.init(&settings).is_err());
+            assert_eq!(run.mode, TimerMode::Init);
+            assert!(run.paused);
+            assert!(run.state_process(&settings).is_ok());
+        });
+    }
+
+    #[test]
+    fn restore_backup_keeps_current_settings() {
+        with_config_dir("restore", b"", || {
+            let original = Settings::template();
+            original.save().unwrap();
+            let mut changed = Settings::template();
+            changed.set_timer(crate::presets::find("pomodoro").unwrap().timer());
+            changed.save().unwrap();
+
+            let (restored, remaining) = Settings::restore_backup().unwrap();
+            assert!(restored.changes(&original).is_empty());
+            assert_eq!(remaining, 1);
+            // 戻す前の設定がバックアップに残り、もう一度戻せる
+            assert!(Settings::latest_backup()
+                .unwrap()
+                .changes(&changed)
+                .is_empty());
+            let (restored, _) = Settings::restore_backup().unwrap();
+            assert!(restored.changes(&changed).is_empty());
+        });
+    }
+
+    fn run_data(profile: serde_json::Value) -> (Settings, RunData) {
+        let config = serde_json::json!({
+            "version": crate::migration::CURRENT_VERSION,
+            "active": DEFAULT_PROFILE,
+            "profiles": { DEFAULT_PROFILE: profile },
+        });
+        let (settings, _) = Settings::from_json(&config.to_string()).unwrap();
+        let mut run = RunData::new();
+        run.load_params(&settings);
+        (settings, run)
+    }
+
+    #[test]
+    fn rest_time_adds_fatigue_within_bounds() {
+        let (settings, run) = run_data(serde_json::json!({
+            "fatigue": { "enabled": true, "threshold": "1h", "per_hour": "10m", "recovery": 0.0 },
+            "bounds": { "max": "20m" },
+        }));
+        let curve = settings.timer().curve().rest(minutes(30));
+        let today = DayTotals {
+            work: minutes(90),
+            rest: minutes(0),
+        };
+        let rest = run.rest_time_at(minutes(30), today);
+        assert_eq!(rest.curve, curve);
+        assert_eq!(rest.fatigue, minutes(10));
+        assert_eq!(rest.total, (curve + minutes(10)).min(minutes(20)));
+        assert!(!rest.long_break);
+    }
+
+    #[test]
+    fn calc_rest_time_uses_current_work() {
+        let (settings, mut run) = run_data(serde_json::json!({}));
+        run.mode = TimerMode::Endurance;
+        run.up.add(minutes(30));
+        let rest = run.calc_rest_time();
+        assert_eq!(rest.curve, settings.timer().curve().rest(minutes(30)));
+        assert_eq!(rest.fatigue, std::time::Duration::ZERO);
+    }
+
+    #[test]
+    fn work_time_follows_accounting() {
+        let prepare = |run: &mut RunData| {
+            run.mode = TimerMode::Endurance;
+            run.up.add(minutes(30));
+            run.majitime_worked = minutes(1);
+            run.work_paused = minutes(5);
+        };
+
+        let (_, mut run) = run_data(serde_json::json!({}));
+        prepare(&mut run);
+        assert_eq!(run.work_time(), minutes(30));
+
+        let (_, mut run) = run_data(serde_json::json!({
+            "accounting": { "include_majitime": true }
+        }));
+        prepare(&mut run);
+        assert_eq!(run.work_time(), minutes(31));
+
+        let (_, mut run) = run_data(serde_json::json!({
+            "accounting": { "exclude_pauses": false }
+        }));
+        prepare(&mut run);
+        assert_eq!(run.work_time(), minutes(35));
+    }
+
+    #[test]
+    fn adjust_targets_the_running_timer() {
+        let mut run = RunData::new();
+        assert_eq!(run.adjust(minutes(1), false), None);
+        assert!(run.adjustments.is_empty());
+
+        run.mode = TimerMode::Endurance;
+        run.up.add(minutes(10));
+        assert_eq!(
+            run.adjust(minutes(5), false),
+            Some(history::AdjustTarget::Endurance)
+        );
+        assert_eq!(
+            run.adjust(minutes(2), true),
+            Some(history::AdjustTarget::Endurance)
+        );
+        assert_eq!(run.up.get_time(), minutes(13));
+
+        run.mode = TimerMode::MajiTime;
+        run.sprint = minutes(1);
+        run.adjust(minutes(2), true);
+        assert_eq!(run.sprint, std::time::Duration::ZERO);
+        assert_eq!(run.adjustments.len(), 3);
+        assert!(run.adjustments[1].decreased);
+    }
+
+    #[test]
+    fn finish_session_keeps_pending_laps() {
+        with_config_dir("laps", b"", || {
+            let settings = Settings::template();
+            let mut run = RunData::new();
+            run.init(&settings).unwrap();
+            run.enter(Step::Endurance);
+            assert!(run.lap());
+            run.label_last_lap("レビュー");
+            run.end_session().unwrap();
+
+            let sessions = history::load().unwrap();
+            let laps = &sessions.last().unwrap().laps;
+            assert_eq!(laps.len(), 1);
+            assert_eq!(laps[0].label.as_deref(), Some("レビュー"));
+        });
+    }
+
+    #[test]
+    fn finish_session_keeps_pending_adjustments() {
+        with_config_dir("adjust", b"", || {
+            let settings = Settings::template();
+            let mut run = RunData::new();
+            run.init(&settings).unwrap();
+            assert_eq!(
+                run.adjust(minutes(1), false),
+                Some(history::AdjustTarget::Sprint)
+            );
+            run.end_session().unwrap();
+
+            let sessions = history::load().unwrap();
+            let adjustments = &sessions.last().unwrap().adjustments;
+            assert_eq!(adjustments.len(), 1);
+            assert_eq!(adjustments[0].amount, minutes(1));
+        });
+    }
+
+    #[test]
+    fn merge_adds_new_profile() {
+        let current = Settings::template();
+        let incoming = with_admin();
+        let sections: Vec<String> = current
+            .changes(&incoming)
+            .iter()
+            .map(Change::section)
+            .collect();
+
+        let merged = current.merge(&incoming, &sections).unwrap();
+        assert_eq!(merged.profiles["admin"].timer.l, minutes(10));
+        assert_eq!(merged.profiles["admin"].timer.k, 0.01);
+    }
+
+    #[test]
+    fn merge_takes_only_selected_sections() {
+        let current = Settings::template();
+        let mut incoming = with_admin();
+        incoming.profiles.get_mut("admin").unwrap().other.remind = minutes(3);
+
+        let merged = current
+            .merge(&incoming, &["profiles.admin.timer".to_string()])
+            .unwrap();
+        let admin = &merged.profiles["admin"];
+        assert_eq!(admin.timer.w0, minutes(20));
+        assert_eq!(admin.other.remind, Other::template().remind);
+    }
+
+    #[test]
+    fn merge_keeps_active_without_its_profile() {
+        let current = Settings::template();
+        let mut incoming = with_admin();
+        incoming.active = "admin".to_string();
+
+        let merged = current.merge(&incoming, &["active".to_string()]).unwrap();
+        assert_eq!(merged.active, DEFAULT_PROFILE);
+
+        let merged = current
+            .merge(
+                &incoming,
+                &["active".to_string(), "profiles.admin.timer".to_string()],
+            )
+            .unwrap();
+        assert_eq!(merged.active, "admin");
+    }
 }
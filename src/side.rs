@@ -0,0 +1,292 @@
+use std::time;
+
+use ratatui::style::Stylize;
+use ratatui::text::{Line, Span};
+
+use super::settings::{resolve_path, Other};
+use super::timer::{parse_duration_minutes, CountDownTimer, MyToType, Timer};
+
+/// サイドタイマーの時間の測り方
+enum SideKind {
+    Countdown(CountDownTimer),
+    Stopwatch(Timer),
+}
+
+/// メインのサイクルとは別に動作する名前付きのタイマー
+struct SideTimer {
+    name: String,
+    kind: SideKind,
+    /// カウントダウンが終わったときに再生する音声ファイル。省略した場合は`finish_sound`
+    sound: Option<String>,
+    /// カウントダウンが終わって知らせたか
+    alerted: bool,
+}
+
+impl SideTimer {
+    fn time(&self) -> time::Duration {
+        match &self.kind {
+            SideKind::Countdown(down) => down.get_time(),
+            SideKind::Stopwatch(up) => up.get_time(),
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum SideErr {
+    #[error("音声ファイルの読み込みに失敗しました")]
+    Io(#[from] std::io::Error),
+    #[error("音楽プレイヤーの再生に失敗しました")]
+    Rodio(#[from] rodio::PlayError),
+    #[error("音声出力を開けませんでした")]
+    Stream(#[from] rodio::StreamError),
+}
+
+/// サイドタイマーの一覧
+/// 音声も`RunData`とは別の出力で再生するため、メインのサイクルの音声を止めることはない
+#[derive(Default)]
+pub struct SidePanel {
+    timers: Vec<SideTimer>,
+    selected: usize,
+    rodio: Option<(rodio::OutputStream, rodio::OutputStreamHandle)>,
+    rodio_sinks: Vec<rodio::Sink>,
+}
+
+impl SidePanel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.timers.is_empty()
+    }
+    /// `"ビルド確認 10m"`のような入力からタイマーを追加する
+    /// 最後の単語が時間として読めればカウントダウン、読めなければストップウォッチになる
+    /// `"ビルド確認 10m @sounds/bell.mp3"`のように空白の後の`@`以降を書くと、そのタイマーの音声ファイルになる
+    pub fn add(&mut self, input: &str) {
+        let input = input.trim();
+        let (input, sound) = match input.rsplit_once('@') {
+            Some((rest, sound))
+                if !sound.trim().is_empty() && (rest.is_empty() || rest.ends_with(' ')) =>
+            {
+                (rest.trim(), Some(sound.trim().to_string()))
+            }
+            _ => (input, None),
+        };
+        let (name, duration) = match input.rsplit_once(' ') {
+            Some((name, last)) => match parse_duration_minutes(last) {
+                Ok(duration) => (name.trim(), Some(duration)),
+                Err(_) => (input, None),
+            },
+            None => match parse_duration_minutes(input) {
+                Ok(duration) => ("", Some(duration)),
+                Err(_) => (input, None),
+            },
+        };
+        let name = if name.is_empty() {
+            format!("タイマー{}", self.timers.len() + 1)
+        } else {
+            name.to_string()
+        };
+        let kind = match duration {
+            Some(duration) => {
+                let mut down = CountDownTimer::new();
+                down.init(duration);
+                SideKind::Countdown(down)
+            }
+            None => {
+                let mut up = Timer::new();
+                up.init();
+                SideKind::Stopwatch(up)
+            }
+        };
+        self.timers.push(SideTimer {
+            name,
+            kind,
+            sound,
+            alerted: false,
+        });
+        self.selected = self.timers.len() - 1;
+    }
+    /// 選択しているタイマーを削除する
+    pub fn remove_selected(&mut self) {
+        if self.selected < self.timers.len() {
+            self.timers.remove(self.selected);
+        }
+        self.selected = self.selected.min(self.timers.len().saturating_sub(1));
+    }
+    pub fn select_next(&mut self) {
+        if !self.timers.is_empty() {
+            self.selected = (self.selected + 1) % self.timers.len();
+        }
+    }
+    /// 新たに終わったカウントダウンをタイマーごとの音声 (省略した場合は`finish_sound`) で知らせ、その名前を返す
+    pub fn poll(&mut self, other: &Other) -> Result<Vec<String>, SideErr> {
+        let mut finished = Vec::new();
+        let mut sounds = Vec::new();
+        for timer in &mut self.timers {
+            if let SideKind::Countdown(down) = &timer.kind {
+                if !timer.alerted && down.get_time().is_zero() {
+                    timer.alerted = true;
+                    finished.push(timer.name.clone());
+                    let sound = match &timer.sound {
+                        Some(sound) => Some(sound.clone()),
+                        None => other.get_finish_sound().ok().map(str::to_owned),
+                    };
+                    // 同時に終わったタイマーで同じ音声を重ねて再生しない
+                    if let Some(sound) = sound.filter(|sound| !sounds.contains(sound)) {
+                        sounds.push(sound);
+                    }
+                }
+            }
+        }
+        for sound in &sounds {
+            self.play_sound(sound)?;
+        }
+        Ok(finished)
+    }
+    fn play_sound(&mut self, path: &str) -> Result<(), SideErr> {
+        if self.rodio.is_none() {
+            self.rodio = Some(rodio::OutputStream::try_default()?);
+        }
+        let handle = &self.rodio.as_ref().expect("opened above").1;
+        let file = std::fs::File::open(resolve_path(path))?;
+        let sink = handle.play_once(std::io::BufReader::new(file))?;
+        sink.set_volume(0.1);
+        // 再生が終わったものを片付けてから追加する
+        self.rodio_sinks.retain(|sink| !sink.empty());
+        self.rodio_sinks.push(sink);
+        Ok(())
+    }
+    /// メインの画面の下に表示する一覧
+    pub fn render(&self) -> Vec<Line<'static>> {
+        let mut lines = vec![Line::from(""), Line::from("サイドタイマー".white().bold())];
+        for (index, timer) in self.timers.iter().enumerate() {
+            let (kind, state): (&str, Span<'static>) = match &timer.kind {
+                SideKind::Countdown(_) if timer.alerted => {
+                    ("カウントダウン", "  時間です".red().bold())
+                }
+                SideKind::Countdown(_) => ("カウントダウン", "".into()),
+                SideKind::Stopwatch(_) => ("ストップウォッチ", "".into()),
+            };
+            let text = format!(
+                "{} {}  {} ({})",
+                if index == self.selected { ">" } else { " " },
+                timer.name,
+                timer.time().to_time_string(),
+                kind
+            );
+            lines.push(Line::from(vec![
+                if index == self.selected {
+                    text.bold()
+                } else {
+                    text.into()
+                },
+                state,
+            ]));
+        }
+        lines.push(Line::from(vec![
+            " 追加 ".dark_gray(),
+            "<N> ".blue().bold(),
+            " 選択 ".dark_gray(),
+            "<Tab> ".blue().bold(),
+            " 削除 ".dark_gray(),
+            "<D> ".blue().bold(),
+        ]));
+        lines
+    }
+}
+
+/// サイドタイマーを追加するための入力
+#[derive(Default)]
+pub struct SideTimerInput {
+    input: String,
+}
+
+impl SideTimerInput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn push(&mut self, c: char) {
+        self.input.push(c);
+    }
+    pub fn backspace(&mut self) {
+        self.input.pop();
+    }
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+    /// ratatuiでレンダリングする文字列のリストを返す
+    /// * .0 => 入力欄
+    /// * .1 => キーヒント表示用文字列
+    pub fn render(&self) -> (Vec<Line<'static>>, Vec<Span<'static>>) {
+        (
+            vec![
+                Line::from("サイドタイマーの追加".white().bold()),
+                Line::from(
+                    "名前と時間を入力するとカウントダウン、名前だけならストップウォッチになります",
+                ),
+                Line::from(vec![
+                    "> ".into(),
+                    self.input.clone().black().on_white(),
+                    " (例: ビルド確認 10m、音声を変える場合は ビルド確認 10m @sounds/bell.mp3)"
+                        .dark_gray(),
+                ]),
+            ],
+            vec![
+                " 追加 ".into(),
+                "<Enter> ".blue().bold(),
+                " キャンセル ".into(),
+                "<Esc> ".blue().bold(),
+            ],
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timer::minutes;
+
+    #[test]
+    fn add_parses_name_duration_and_sound() {
+        let mut panel = SidePanel::new();
+        panel.add("ビルド確認 10 @sounds/bell.mp3");
+        panel.add("会議");
+        panel.add("5m");
+
+        let timer = &panel.timers[0];
+        assert_eq!(timer.name, "ビルド確認");
+        assert!(matches!(timer.kind, SideKind::Countdown(_)));
+        assert!(timer.time() <= minutes(10) && timer.time() > minutes(9));
+        assert_eq!(timer.sound.as_deref(), Some("sounds/bell.mp3"));
+
+        assert_eq!(panel.timers[1].name, "会議");
+        assert!(matches!(panel.timers[1].kind, SideKind::Stopwatch(_)));
+        assert_eq!(panel.timers[1].sound, None);
+        assert_eq!(panel.timers[2].name, "タイマー3");
+    }
+
+    #[test]
+    fn selection_follows_removal() {
+        let mut panel = SidePanel::new();
+        panel.add("a");
+        panel.add("b");
+        assert_eq!(panel.selected, 1);
+        panel.select_next();
+        assert_eq!(panel.selected, 0);
+        panel.remove_selected();
+        assert_eq!(panel.timers[0].name, "b");
+        panel.remove_selected();
+        assert!(panel.is_empty());
+        assert_eq!(panel.selected, 0);
+    }
+
+    #[test]
+    fn poll_reports_each_finished_countdown_once() {
+        let mut panel = SidePanel::new();
+        panel.add("お茶 0s");
+        panel.add("作業 10m");
+        let other = Other::default();
+        assert_eq!(panel.poll(&other).unwrap(), vec!["お茶".to_string()]);
+        assert!(panel.poll(&other).unwrap().is_empty());
+    }
+}
@@ -41,6 +41,17 @@ impl Timer {
     pub fn is_paused(&self) -> bool {
         self.paused
     }
+    /// 経過時間を`amount`だけ増やす
+    pub fn add(&mut self, amount: time::Duration) {
+        self.elapsed_time += amount;
+    }
+    /// 経過時間を`amount`だけ減らす。0より短くはならない
+    pub fn sub(&mut self, amount: time::Duration) {
+        self.elapsed_time = self.get_time().saturating_sub(amount);
+        if !self.paused {
+            self.start = Some(time::Instant::now());
+        }
+    }
 }
 
 pub trait MyToType {
@@ -93,4 +104,239 @@ impl CountDownTimer {
     pub fn is_paused(&self) -> bool {
         self.core.is_paused()
     }
+    /// 残り時間を`amount`だけ増やす
+    pub fn extend(&mut self, amount: time::Duration) {
+        self.limit += amount;
+    }
+    /// 残り時間を`amount`だけ減らす。残り時間が無くなった場合はそこで終わる
+    pub fn shorten(&mut self, amount: time::Duration) {
+        self.limit = self.limit.saturating_sub(amount);
+    }
+}
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum ParseDurationErr {
+    #[error("時間が空です")]
+    Empty,
+    #[error("{0:?}の数値が読み取れません")]
+    InvalidNumber(String),
+    #[error("{0:?}は時間の単位ではありません (h, m, sが使えます)")]
+    InvalidUnit(String),
+    #[error("{0:?}は長すぎます")]
+    TooLong(String),
+}
+
+/// `parse_duration`と同じ形式の文字列を時間に変換する。ただし単位の無い数値は分として扱う
+pub fn parse_duration_minutes(input: &str) -> Result<time::Duration, ParseDurationErr> {
+    let input = input.trim();
+    if !input.is_empty() && input.chars().all(|c| c.is_ascii_digit()) {
+        let minutes: u64 = input
+            .parse()
+            .map_err(|_| ParseDurationErr::InvalidNumber(input.to_string()))?;
+        return minutes
+            .checked_mul(60)
+            .map(time::Duration::from_secs)
+            .ok_or_else(|| ParseDurationErr::TooLong(input.to_string()));
+    }
+    parse_duration(input)
+}
+
+/// `"1h30m"`, `"40m"`, `"1m30s"`, `"90"`(秒) のような文字列を時間に変換する
+pub fn parse_duration(input: &str) -> Result<time::Duration, ParseDurationErr> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err(ParseDurationErr::Empty);
+    }
+    // 単位の無い数値は秒として扱う
+    if input.chars().all(|c| c.is_ascii_digit()) {
+        return input
+            .parse()
+            .map(time::Duration::from_secs)
+            .map_err(|_| ParseDurationErr::InvalidNumber(input.to_string()));
+    }
+
+    let mut total: u64 = 0;
+    let mut rest = input;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let number: u64 = rest[..digits]
+            .parse()
+            .map_err(|_| ParseDurationErr::InvalidNumber(rest.to_string()))?;
+        rest = rest[digits..].trim_start();
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit() || c.is_whitespace())
+            .unwrap_or(rest.len());
+        let scale = match &rest[..unit_len] {
+            "h" => 3600,
+            "m" => 60,
+            "s" => 1,
+            unit => return Err(ParseDurationErr::InvalidUnit(unit.to_string())),
+        };
+        total = number
+            .checked_mul(scale)
+            .and_then(|secs| total.checked_add(secs))
+            .ok_or_else(|| ParseDurationErr::TooLong(input.to_string()))?;
+        rest = rest[unit_len..].trim_start();
+    }
+    Ok(time::Duration::from_secs(total))
+}
+
+/// `parse_duration`で読み取れる形式の文字列に変換する
+pub fn format_duration(duration: &time::Duration) -> String {
+    let all_times = duration.as_secs();
+    if all_times == 0 {
+        return "0s".to_string();
+    }
+
+    let hours = all_times / 3600;
+    let mins = (all_times % 3600) / 60;
+    let secs = all_times % 60;
+
+    let mut output = String::new();
+    if hours > 0 {
+        output += &format!("{}h", hours);
+    }
+    if mins > 0 {
+        output += &format!("{}m", mins);
+    }
+    if secs > 0 {
+        output += &format!("{}s", secs);
+    }
+    output
+}
+
+/// 設定ファイルで時間を`"1m30s"`のような文字列として読み書きするためのモジュール
+/// `#[serde(with = "human_duration")]`として使う。読み込み時は秒数の整数も受け付ける
+pub mod human_duration {
+    use serde::{de, Deserializer, Serializer};
+    use std::time;
+
+    pub fn serialize<S: Serializer>(
+        duration: &time::Duration,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&super::format_duration(duration))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<time::Duration, D::Error> {
+        struct Visitor;
+
+        impl de::Visitor<'_> for Visitor {
+            type Value = time::Duration;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("\"1m30s\"のような時間の文字列か秒数")
+            }
+            fn visit_u64<E: de::Error>(self, secs: u64) -> Result<Self::Value, E> {
+                Ok(time::Duration::from_secs(secs))
+            }
+            fn visit_str<E: de::Error>(self, input: &str) -> Result<Self::Value, E> {
+                super::parse_duration(input).map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+/// `human_duration`の`Option`版。`None`は`null`として書き込む
+pub mod human_duration_option {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time;
+
+    pub fn serialize<S: Serializer>(
+        duration: &Option<time::Duration>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match duration {
+            Some(duration) => super::human_duration::serialize(duration, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<time::Duration>, D::Error> {
+        #[derive(Deserialize)]
+        struct Wrapper(#[serde(with = "super::human_duration")] time::Duration);
+
+        Ok(Option::<Wrapper>::deserialize(deserializer)?.map(|Wrapper(duration)| duration))
+    }
+}
+
+/// テストで時間を分単位で書くための関数
+#[cfg(test)]
+pub fn minutes(minutes: u64) -> time::Duration {
+    time::Duration::from_secs(minutes * 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_duration_units() {
+        assert_eq!(parse_duration("90"), Ok(time::Duration::from_secs(90)));
+        assert_eq!(
+            parse_duration("40m"),
+            Ok(time::Duration::from_secs(40 * 60))
+        );
+        assert_eq!(
+            parse_duration("1h 30m 5s"),
+            Ok(time::Duration::from_secs(3600 + 30 * 60 + 5))
+        );
+        assert_eq!(parse_duration(" "), Err(ParseDurationErr::Empty));
+        assert_eq!(
+            parse_duration("3d"),
+            Err(ParseDurationErr::InvalidUnit("d".to_string()))
+        );
+        assert!(matches!(
+            parse_duration("m"),
+            Err(ParseDurationErr::InvalidNumber(_))
+        ));
+    }
+
+    #[test]
+    fn parse_duration_minutes_without_unit() {
+        assert_eq!(
+            parse_duration_minutes("15"),
+            Ok(time::Duration::from_secs(15 * 60))
+        );
+        assert_eq!(
+            parse_duration_minutes("90s"),
+            Ok(time::Duration::from_secs(90))
+        );
+    }
+
+    #[test]
+    fn parse_duration_rejects_overflow() {
+        assert!(matches!(
+            parse_duration("9999999999999999h"),
+            Err(ParseDurationErr::TooLong(_))
+        ));
+        assert!(matches!(
+            parse_duration(&format!("{}s1s", u64::MAX)),
+            Err(ParseDurationErr::TooLong(_))
+        ));
+        assert!(matches!(
+            parse_duration_minutes("999999999999999999"),
+            Err(ParseDurationErr::TooLong(_))
+        ));
+    }
+
+    #[test]
+    fn format_duration_round_trips() {
+        for secs in [0, 1, 59, 60, 61, 3599, 3600, 3661, 86400, 100 * 3600 + 1] {
+            let duration = time::Duration::from_secs(secs);
+            assert_eq!(parse_duration(&format_duration(&duration)), Ok(duration));
+        }
+        assert_eq!(
+            format_duration(&time::Duration::from_secs(5430)),
+            "1h30m30s"
+        );
+    }
 }
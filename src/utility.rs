@@ -0,0 +1,109 @@
+use std::time;
+
+use ratatui::style::Stylize;
+use ratatui::text::{Line, Span};
+
+use super::timer::parse_duration_minutes;
+
+/// カウントダウンの時間の入力
+#[derive(Default)]
+pub struct DurationInput {
+    input: String,
+    error: Option<String>,
+}
+
+impl DurationInput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn push(&mut self, c: char) {
+        self.input.push(c);
+    }
+    pub fn backspace(&mut self) {
+        self.input.pop();
+    }
+    /// 入力を時間に変換する。単位の無い数値は分として扱う
+    /// 変換できない場合はエラーを表示して`None`を返す
+    pub fn submit(&mut self) -> Option<time::Duration> {
+        match parse_duration_minutes(&self.input) {
+            Ok(duration) if !duration.is_zero() => Some(duration),
+            Ok(_) => {
+                self.error = Some("0秒より長い時間を入力してください".to_string());
+                None
+            }
+            Err(err) => {
+                self.error = Some(err.to_string());
+                None
+            }
+        }
+    }
+    /// ratatuiでレンダリングする文字列のリストを返す
+    /// * .0 => 入力欄
+    /// * .1 => キーヒント表示用文字列
+    pub fn render(&self) -> (Vec<Line<'static>>, Vec<Span<'static>>) {
+        let mut lines = vec![
+            Line::from("カウントダウン".white().bold()),
+            Line::from("カウントダウンする時間を入力してください"),
+            Line::from(vec![
+                "> ".into(),
+                self.input.clone().black().on_white(),
+                " (例: 15、1h30m。単位の無い数値は分)".dark_gray(),
+            ]),
+        ];
+        if let Some(error) = &self.error {
+            lines.push(Line::from(error.clone().yellow()));
+        }
+        (
+            lines,
+            vec![
+                " 開始 ".into(),
+                "<Enter> ".blue().bold(),
+                " キャンセル ".into(),
+                "<Esc> ".blue().bold(),
+            ],
+        )
+    }
+}
+
+/// 耐久モードで記録したラップの名前の入力
+#[derive(Default)]
+pub struct LapLabelInput {
+    input: String,
+}
+
+impl LapLabelInput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn push(&mut self, c: char) {
+        self.input.push(c);
+    }
+    pub fn backspace(&mut self) {
+        self.input.pop();
+    }
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+    /// ratatuiでレンダリングする文字列のリストを返す
+    /// * .0 => 入力欄
+    /// * .1 => キーヒント表示用文字列
+    pub fn render(&self) -> (Vec<Line<'static>>, Vec<Span<'static>>) {
+        (
+            vec![
+                Line::from("ラップの名前".white().bold()),
+                Line::from("ラップを記録しました。名前を付ける場合は入力してください"),
+                Line::from(vec![
+                    "> ".into(),
+                    self.input.clone().black().on_white(),
+                    " (例: レビュー対応)".dark_gray(),
+                ]),
+            ],
+            vec![
+                " 決定 ".into(),
+                "<Enter> ".blue().bold(),
+                " 名前を付けない ".into(),
+                "<Esc> ".blue().bold(),
+            ],
+        )
+    }
+}
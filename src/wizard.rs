@@ -0,0 +1,244 @@
+use std::time;
+
+use ratatui::style::Stylize;
+use ratatui::text::{Line, Span};
+
+use super::curve::{RestBounds, RestCurve};
+use super::settings::Timer;
+use super::timer::{format_duration, parse_duration_minutes, MyToType};
+
+/// 質問文と、回答が無い場合の値
+const QUESTIONS: [(&str, time::Duration); 3] = [
+    (
+        "集中して作業を続けられるのは、ふだん何分くらいですか？",
+        time::Duration::from_secs(40 * 60),
+    ),
+    (
+        "1時間作業した後には、何分くらい休憩したいですか？",
+        time::Duration::from_secs(25 * 60),
+    ),
+    (
+        "どれだけ長く作業しても、休憩は最大で何分あれば十分ですか？",
+        time::Duration::from_secs(40 * 60),
+    ),
+];
+
+/// 初回起動時に、いくつかの質問への回答から休憩時間の曲線のパラメーターを決める
+pub struct Wizard {
+    step: usize,
+    input: String,
+    answers: [Option<time::Duration>; QUESTIONS.len()],
+    error: Option<String>,
+}
+
+impl Wizard {
+    pub fn new() -> Self {
+        Self {
+            step: 0,
+            input: String::new(),
+            answers: [None; QUESTIONS.len()],
+            error: None,
+        }
+    }
+    /// 全ての質問に回答し、結果の確認中であれば`true`
+    pub fn is_finished(&self) -> bool {
+        self.step >= QUESTIONS.len()
+    }
+    pub fn push(&mut self, c: char) {
+        if !self.is_finished() {
+            self.input.push(c);
+        }
+    }
+    pub fn backspace(&mut self) {
+        self.input.pop();
+    }
+    /// 入力中の回答を確定して次の質問へ進む。空の場合は例の値を使う
+    pub fn submit(&mut self) {
+        if self.is_finished() {
+            return;
+        }
+        let input = self.input.trim();
+        let answer = if input.is_empty() {
+            Ok(QUESTIONS[self.step].1)
+        } else {
+            parse_duration_minutes(input).map_err(|err| err.to_string())
+        };
+        match answer {
+            Ok(answer) if !answer.is_zero() => {
+                self.answers[self.step] = Some(answer);
+                self.step += 1;
+                self.input.clear();
+                self.error = None;
+            }
+            Ok(_) => self.error = Some("0分より長い時間を入力してください".to_string()),
+            Err(err) => self.error = Some(err),
+        }
+    }
+    /// 1つ前の質問に戻る
+    pub fn back(&mut self) {
+        if self.step > 0 {
+            self.step -= 1;
+            self.input = self.answers[self.step]
+                .map(|answer| format_duration(&answer))
+                .unwrap_or_default();
+            self.error = None;
+        }
+    }
+    fn answer(&self, index: usize) -> time::Duration {
+        self.answers[index].unwrap_or(QUESTIONS[index].1)
+    }
+    /// 回答に合う休憩時間の曲線
+    /// * Err => 回答が矛盾していて傾きを決められなかった場合の、代わりに使う曲線
+    pub fn curve(&self, base: &Timer) -> Result<RestCurve, RestCurve> {
+        let w0 = self.answer(0);
+        let rest_after_hour = self.answer(1);
+        let l = self.answer(2);
+
+        RestCurve::fit_slope(l, w0, time::Duration::from_secs(60 * 60), rest_after_hour).ok_or(
+            RestCurve {
+                l,
+                k: base.curve().k,
+                w0,
+            },
+        )
+    }
+    /// 回答に合わせて曲線を置き換えた`Timer`
+    pub fn timer(&self, base: &Timer) -> Timer {
+        match self.curve(base) {
+            Ok(curve) | Err(curve) => base.with_curve(curve),
+        }
+    }
+    /// ratatuiでレンダリングする文字列のリストを返す
+    /// * .0 => 質問と回答、曲線のプレビュー
+    /// * .1 => キーヒント表示用文字列
+    pub fn render(
+        &self,
+        base: &Timer,
+        bounds: &RestBounds,
+    ) -> (Vec<Line<'static>>, Vec<Span<'static>>) {
+        let mut lines = vec![
+            Line::from("はじめての設定".white().bold()),
+            Line::from("いくつかの質問に答えると、休憩時間の曲線 (L, k, w0) を決めます"),
+            Line::from(""),
+        ];
+        for (index, (question, example)) in QUESTIONS.iter().enumerate() {
+            if index < self.step {
+                lines.push(Line::from(format!(
+                    "{} {}",
+                    question,
+                    format_duration(&self.answer(index))
+                )));
+            } else if index == self.step {
+                lines.push(Line::from(question.bold()));
+                lines.push(Line::from(vec![
+                    "> ".into(),
+                    self.input.clone().black().on_white(),
+                    format!(" (例: {}、空欄なら例の値)", format_duration(example)).dark_gray(),
+                ]));
+            }
+        }
+        if let Some(error) = &self.error {
+            lines.push(Line::from(error.clone().yellow()));
+        }
+
+        lines.push(Line::from(""));
+        let curve = self.curve(base);
+        if curve.is_err() {
+            lines.push(Line::from(
+                "回答から曲線の傾きを決められないため、現在の傾きを使います".yellow(),
+            ));
+        }
+        let curve = curve.unwrap_or_else(|curve| curve);
+        lines.push(Line::from(format!(
+            "L = {}  k = {:.5}  w0 = {}",
+            curve.l.to_time_string(),
+            curve.k,
+            curve.w0.to_time_string()
+        )));
+        lines.push(Line::from(format!("休憩時間: {}", curve.summary(bounds))));
+
+        let hints = if self.is_finished() {
+            vec![
+                " この設定で始める ".into(),
+                "<Enter> ".blue().bold(),
+                " 戻る ".into(),
+                "<Backspace> ".blue().bold(),
+                " スキップ ".into(),
+                "<Esc> ".blue().bold(),
+            ]
+        } else {
+            vec![
+                " 次へ ".into(),
+                "<Enter> ".blue().bold(),
+                " 戻る ".into(),
+                "<↑> ".blue().bold(),
+                " スキップ ".into(),
+                "<Esc> ".blue().bold(),
+            ]
+        };
+        (lines, hints)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timer::minutes;
+
+    fn base() -> Timer {
+        Timer::new(minutes(1), minutes(40), 0.0017, minutes(40))
+    }
+
+    fn answer(wizard: &mut Wizard, input: &str) {
+        for c in input.chars() {
+            wizard.push(c);
+        }
+        wizard.submit();
+    }
+
+    #[test]
+    fn blank_answers_use_examples() {
+        let mut wizard = Wizard::new();
+        for _ in QUESTIONS {
+            answer(&mut wizard, "");
+        }
+        assert!(wizard.is_finished());
+        let curve = wizard.curve(&base()).expect("例の値は矛盾しない");
+        assert_eq!(curve.l, minutes(40));
+        assert_eq!(curve.w0, minutes(40));
+        let rest = curve.rest(minutes(60)).as_secs_f64();
+        assert!((rest - minutes(25).as_secs_f64()).abs() < 1.0);
+    }
+
+    #[test]
+    fn invalid_answers_stay_on_the_question() {
+        let mut wizard = Wizard::new();
+        answer(&mut wizard, "abc");
+        assert_eq!(wizard.step, 0);
+        assert!(wizard.error.is_some());
+        wizard.input.clear();
+        answer(&mut wizard, "0");
+        assert_eq!(wizard.step, 0);
+
+        wizard.input.clear();
+        answer(&mut wizard, "30");
+        assert_eq!(wizard.step, 1);
+        assert!(wizard.error.is_none());
+        // 戻ると前の回答を編集できる
+        wizard.back();
+        assert_eq!(wizard.step, 0);
+        assert_eq!(wizard.input, "30m");
+    }
+
+    #[test]
+    fn contradicting_answers_keep_the_current_slope() {
+        let mut wizard = Wizard::new();
+        // 集中できる時間より後なのに、最大値の半分より短い休憩
+        for input in ["40", "10", "40"] {
+            answer(&mut wizard, input);
+        }
+        let curve = wizard.curve(&base()).unwrap_err();
+        assert_eq!(curve.k, base().curve().k);
+        assert_eq!(wizard.timer(&base()).curve(), curve);
+    }
+}
@@ -0,0 +1,203 @@
+use serde::{Deserialize, Serialize};
+use std::time;
+
+/// サイクルの段階。`RunData`はこの段階に対応する`TimerMode`で動作する
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Step {
+    /// 決まった時間だけ作業するカウントダウン (`TimerMode::MajiTime`)
+    Sprint(time::Duration),
+    /// 手動で終えるまで作業するカウントアップ (`TimerMode::Endurance`)
+    Endurance,
+    /// 休憩 (`TimerMode::Rest`)
+    Rest,
+}
+
+/// 手法が決める休憩時間
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WorkflowRest {
+    pub time: time::Duration,
+    /// 一定のサイクルごとに取る長い休憩か
+    pub long_break: bool,
+}
+
+/// 作業と休憩のサイクルの進め方
+/// 画面の表示、音声、履歴の記録は`RunData`が共通で行い、手法はどの段階へ進むかと休憩時間だけを決める
+pub trait Workflow {
+    /// 画面に表示する手法の名前
+    fn name(&self) -> &'static str;
+    /// サイクルの最初の段階
+    /// * majitime => 設定されている本気モードの時間
+    fn start(&self, majitime: time::Duration) -> Step;
+    /// `Step::Sprint`のカウントダウンを終えたときの次の段階
+    fn after_sprint(&self) -> Step;
+    /// `cycle`番目 (1から数える) のサイクルで`work`だけ作業したときの休憩時間
+    /// `None`の場合は休憩時間の曲線 (疲労、下限・上限、長い休憩の設定を含む) で算出する
+    fn rest(&self, work: time::Duration, cycle: u32) -> Option<WorkflowRest>;
+}
+
+/// 本気モード → 耐久モード → 作業時間に応じた休憩
+pub struct Maji;
+
+impl Workflow for Maji {
+    fn name(&self) -> &'static str {
+        "本気タイマー"
+    }
+    fn start(&self, majitime: time::Duration) -> Step {
+        Step::Sprint(majitime)
+    }
+    fn after_sprint(&self) -> Step {
+        Step::Endurance
+    }
+    fn rest(&self, _work: time::Duration, _cycle: u32) -> Option<WorkflowRest> {
+        None
+    }
+}
+
+/// 決まった時間の作業と休憩を繰り返し、`long_every`サイクルごとに長い休憩を取る
+pub struct Fixed {
+    name: &'static str,
+    work: time::Duration,
+    rest: time::Duration,
+    long_rest: time::Duration,
+    long_every: u32,
+}
+
+impl Workflow for Fixed {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+    fn start(&self, _majitime: time::Duration) -> Step {
+        Step::Sprint(self.work)
+    }
+    fn after_sprint(&self) -> Step {
+        Step::Rest
+    }
+    fn rest(&self, _work: time::Duration, cycle: u32) -> Option<WorkflowRest> {
+        let long_break = self.long_every > 0 && cycle.is_multiple_of(self.long_every);
+        Some(WorkflowRest {
+            time: if long_break {
+                self.long_rest
+            } else {
+                self.rest
+            },
+            long_break,
+        })
+    }
+}
+
+/// 手動で終えるまで作業し、作業時間の`1 / ratio`だけ休憩する
+pub struct Flowtime {
+    ratio: u32,
+}
+
+impl Workflow for Flowtime {
+    fn name(&self) -> &'static str {
+        "Flowtime"
+    }
+    fn start(&self, _majitime: time::Duration) -> Step {
+        Step::Endurance
+    }
+    fn after_sprint(&self) -> Step {
+        Step::Endurance
+    }
+    fn rest(&self, work: time::Duration, _cycle: u32) -> Option<WorkflowRest> {
+        Some(WorkflowRest {
+            time: work / self.ratio,
+            long_break: false,
+        })
+    }
+}
+
+const MAJI: Maji = Maji;
+const POMODORO: Fixed = Fixed {
+    name: "ポモドーロ",
+    work: time::Duration::from_secs(25 * 60),
+    rest: time::Duration::from_secs(5 * 60),
+    long_rest: time::Duration::from_secs(15 * 60),
+    long_every: 4,
+};
+const FIFTY_TWO_SEVENTEEN: Fixed = Fixed {
+    name: "52/17",
+    work: time::Duration::from_secs(52 * 60),
+    rest: time::Duration::from_secs(17 * 60),
+    long_rest: time::Duration::from_secs(17 * 60),
+    long_every: 0,
+};
+const FLOWTIME: Flowtime = Flowtime { ratio: 5 };
+
+/// 設定ファイルで選ぶ手法
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkflowKind {
+    /// 本気モード → 耐久モード → 作業時間に応じた休憩
+    #[default]
+    Maji,
+    /// 25分の作業と5分の休憩、4サイクルごとに15分の休憩
+    Pomodoro,
+    /// 手動で終えるまで作業し、作業時間の1/5だけ休憩
+    Flowtime,
+    /// 52分の作業と17分の休憩
+    #[serde(rename = "52/17")]
+    FiftyTwoSeventeen,
+}
+
+impl WorkflowKind {
+    pub fn workflow(&self) -> &'static dyn Workflow {
+        match self {
+            Self::Maji => &MAJI,
+            Self::Pomodoro => &POMODORO,
+            Self::Flowtime => &FLOWTIME,
+            Self::FiftyTwoSeventeen => &FIFTY_TWO_SEVENTEEN,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timer::minutes;
+
+    #[test]
+    fn maji_leaves_rest_to_the_curve() {
+        let maji = WorkflowKind::Maji.workflow();
+        assert_eq!(maji.start(minutes(1)), Step::Sprint(minutes(1)));
+        assert_eq!(maji.after_sprint(), Step::Endurance);
+        assert_eq!(maji.rest(minutes(30), 1), None);
+    }
+
+    #[test]
+    fn pomodoro_takes_a_long_rest_every_fourth_cycle() {
+        let pomodoro = WorkflowKind::Pomodoro.workflow();
+        assert_eq!(pomodoro.start(minutes(1)), Step::Sprint(minutes(25)));
+        assert_eq!(pomodoro.after_sprint(), Step::Rest);
+        let rest = |cycle| pomodoro.rest(minutes(25), cycle).unwrap();
+        assert_eq!(rest(3).time, minutes(5));
+        assert!(!rest(3).long_break);
+        assert_eq!(rest(4).time, minutes(15));
+        assert!(rest(4).long_break);
+        // 長い休憩を取らない手法
+        let rest = WorkflowKind::FiftyTwoSeventeen
+            .workflow()
+            .rest(minutes(52), 4)
+            .unwrap();
+        assert_eq!(rest.time, minutes(17));
+        assert!(!rest.long_break);
+    }
+
+    #[test]
+    fn flowtime_rests_a_fifth_of_work() {
+        let flowtime = WorkflowKind::Flowtime.workflow();
+        assert_eq!(flowtime.start(minutes(1)), Step::Endurance);
+        assert_eq!(flowtime.rest(minutes(50), 1).unwrap().time, minutes(10));
+    }
+
+    #[test]
+    fn kind_uses_config_names() {
+        let kind: WorkflowKind = serde_json::from_str("\"52/17\"").unwrap();
+        assert_eq!(kind, WorkflowKind::FiftyTwoSeventeen);
+        assert_eq!(
+            serde_json::to_string(&WorkflowKind::Pomodoro).unwrap(),
+            "\"pomodoro\""
+        );
+    }
+}